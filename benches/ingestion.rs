@@ -0,0 +1,82 @@
+//! Ingestion throughput with several concurrent inputs, each read on its own reader
+//! thread and applied to one shared engine. Run with `cargo bench --bench ingestion`.
+
+use std::io::Write;
+use std::time::Instant;
+
+use payments_engine::engine::payments_engine::PaymentsEngine;
+use payments_engine::ingest::csv_reader::spawn_reader;
+use tokio::task::JoinSet;
+
+const ROWS_PER_INPUT: u32 = 50_000;
+const READ_AHEAD_ROWS: usize = 1024;
+
+/// Deposits and withdrawals for a client of its own per input, with unique ids. Each
+/// withdrawal takes less than the deposit before it, so every row is applied.
+fn write_input(index: u32) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "ingestion_bench_{}_{}.csv",
+        std::process::id(),
+        index
+    ));
+    let mut file = std::io::BufWriter::new(std::fs::File::create(&path).unwrap());
+    writeln!(file, "type,client,tx,amount").unwrap();
+    for row in 0..ROWS_PER_INPUT {
+        let transaction_id = index * ROWS_PER_INPUT + row;
+        let (t_type, amount) = if row % 2 == 0 {
+            ("deposit", "1.2500")
+        } else {
+            ("withdrawal", "1.0000")
+        };
+        writeln!(
+            file,
+            "{}, {}, {}, {}",
+            t_type, index, transaction_id, amount
+        )
+        .unwrap();
+    }
+    path
+}
+
+async fn ingest(inputs: &[std::path::PathBuf]) {
+    let payments_engine = PaymentsEngine::new();
+    let mut set = JoinSet::new();
+    for path in inputs {
+        let file = std::io::BufReader::new(std::fs::File::open(path).unwrap());
+        let mut rows = spawn_reader(file, None, READ_AHEAD_ROWS).unwrap();
+        let payments_engine = payments_engine.clone();
+        set.spawn(async move {
+            while let Some(row) = rows.recv().await {
+                // A refused row would measure the error path instead
+                payments_engine
+                    .handle_transaction(row.transaction.unwrap())
+                    .await
+                    .unwrap();
+            }
+        });
+    }
+    set.join_all().await;
+}
+
+fn main() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    for concurrent in [1, 4, 16, 64] {
+        let inputs: Vec<_> = (0..concurrent).map(write_input).collect();
+
+        let start = Instant::now();
+        runtime.block_on(ingest(&inputs));
+        let elapsed = start.elapsed();
+
+        let rows = concurrent as f64 * ROWS_PER_INPUT as f64;
+        println!(
+            "{:>3} inputs: {:>9} rows in {:>8.2?}, {:>10.0} rows/s",
+            concurrent,
+            rows,
+            elapsed,
+            rows / elapsed.as_secs_f64()
+        );
+        for path in inputs {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+}
@@ -0,0 +1,11 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    #[cfg(feature = "grpc")]
+    {
+        // The vendored protoc, so that none needs to be installed to build
+        let protoc = protoc_bin_vendored::protoc_bin_path()?;
+        // SAFETY: the build script is single threaded
+        unsafe { std::env::set_var("PROTOC", protoc) };
+        tonic_prost_build::compile_protos("schemas/payments.proto")?;
+    }
+    Ok(())
+}
@@ -0,0 +1,110 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::access::error::AccessListError;
+use crate::types::ClientId;
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum ListKind {
+    Block,
+    Allow,
+}
+
+#[derive(Debug, Deserialize)]
+struct AccessEntry {
+    list: ListKind,
+    client: ClientId,
+}
+
+/// Clients that may transact. Blocked clients are always refused; once any client is
+/// allowed, every client that is not allowed is refused as well.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClientAccessList {
+    blocked: HashSet<ClientId>,
+    allowed: Option<HashSet<ClientId>>,
+}
+
+impl ClientAccessList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads a `list,client` CSV file where `list` is either `block` or `allow`.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, AccessListError> {
+        Self::from_reader(std::fs::File::open(path).map_err(csv::Error::from)?)
+    }
+
+    pub fn from_reader(reader: impl std::io::Read) -> Result<Self, AccessListError> {
+        let mut rdr = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(reader);
+
+        let mut access_list = Self::new();
+        for entry in rdr.deserialize::<AccessEntry>() {
+            let entry = entry?;
+            match entry.list {
+                ListKind::Block => access_list.block(entry.client),
+                ListKind::Allow => access_list.allow(entry.client),
+            }
+        }
+        Ok(access_list)
+    }
+
+    pub fn block(&mut self, client_id: ClientId) {
+        self.blocked.insert(client_id);
+    }
+
+    pub fn allow(&mut self, client_id: ClientId) {
+        self.allowed
+            .get_or_insert_with(HashSet::new)
+            .insert(client_id);
+    }
+
+    pub fn is_allowed(&self, client_id: ClientId) -> bool {
+        !self.blocked.contains(&client_id)
+            && self
+                .allowed
+                .as_ref()
+                .is_none_or(|allowed| allowed.contains(&client_id))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_access_list() {
+        let access_list = ClientAccessList::new();
+
+        assert!(access_list.is_allowed(1));
+    }
+
+    #[test]
+    fn blocklist() {
+        let access_list =
+            ClientAccessList::from_reader("list, client\nblock, 5\n".as_bytes()).unwrap();
+
+        assert!(!access_list.is_allowed(5));
+        assert!(access_list.is_allowed(6));
+    }
+
+    #[test]
+    fn allowlist() {
+        let access_list =
+            ClientAccessList::from_reader("list,client\nallow,1\nallow,2\nblock,2\n".as_bytes())
+                .unwrap();
+
+        assert!(access_list.is_allowed(1));
+        assert!(!access_list.is_allowed(2));
+        assert!(!access_list.is_allowed(3));
+    }
+
+    #[test]
+    fn invalid_access_list() {
+        assert!(ClientAccessList::from_reader("list,client\ndeny,1\n".as_bytes()).is_err());
+    }
+}
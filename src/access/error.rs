@@ -0,0 +1,7 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum AccessListError {
+    #[error("Error reading access list: {0}")]
+    Read(#[from] csv::Error),
+}
@@ -0,0 +1,34 @@
+use thiserror::Error;
+
+use crate::types::TransactionId;
+
+#[derive(Error, Debug)]
+pub enum BinaryError {
+    #[error("Error accessing binary transactions: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Not a binary transactions file")]
+    BadMagic,
+
+    #[error("Unsupported binary format version {0} with {1} byte records")]
+    UnsupportedVersion(u16, u16),
+
+    #[error("Unknown transaction type tag {0}")]
+    UnknownType(u8),
+
+    #[error("Invalid amount for transaction {0}: {1}")]
+    InvalidAmount(TransactionId, &'static str),
+
+    #[error("Transaction {0} has a timestamp, which binary format version 1 can not store")]
+    TimestampNotSupported(TransactionId),
+}
+
+impl BinaryError {
+    /// Whether the rest of the input can not be read anymore, as opposed to one bad record.
+    pub fn is_fatal(&self) -> bool {
+        matches!(
+            self,
+            BinaryError::Io(_) | BinaryError::BadMagic | BinaryError::UnsupportedVersion(..)
+        )
+    }
+}
@@ -0,0 +1,30 @@
+//! Compact binary transaction format, for feeds where CSV and decimal parsing dominate.
+//!
+//! A file starts with an 8 byte header followed by fixed-size 16 byte records. All
+//! integers are little-endian.
+//!
+//! Header:
+//!
+//! | offset | size | field                         |
+//! |-------:|-----:|-------------------------------|
+//! | 0      | 4    | magic `PETX`                  |
+//! | 4      | 2    | format version, currently `1` |
+//! | 6      | 2    | record size, currently `16`   |
+//!
+//! Record:
+//!
+//! | offset | size | field                                                          |
+//! |-------:|-----:|----------------------------------------------------------------|
+//! | 0      | 1    | type tag, see [`record::type_tag`]                             |
+//! | 1      | 1    | flags, bit 0 set when the row has an amount                    |
+//! | 2      | 2    | client id, `u16`                                               |
+//! | 4      | 4    | transaction id, `u32`                                          |
+//! | 8      | 8    | amount in ten-thousandths, `i64`, zero when there is no amount |
+//!
+//! Amounts follow the same rules as in CSV: non-negative, at most four decimals.
+//! Timestamps are not part of version 1.
+
+pub mod error;
+pub mod record;
+pub mod record_reader;
+pub mod record_writer;
@@ -0,0 +1,205 @@
+use rust_decimal::Decimal;
+
+use crate::binary::error::BinaryError;
+use crate::transaction::{AMOUNT_SCALE, Transaction, Type, validate_amount};
+
+pub const MAGIC: [u8; 4] = *b"PETX";
+pub const VERSION: u16 = 1;
+pub const HEADER_SIZE: usize = 8;
+pub const RECORD_SIZE: usize = 16;
+
+const HAS_AMOUNT: u8 = 0b1;
+
+pub fn header() -> [u8; HEADER_SIZE] {
+    let mut header = [0; HEADER_SIZE];
+    header[0..4].copy_from_slice(&MAGIC);
+    header[4..6].copy_from_slice(&VERSION.to_le_bytes());
+    header[6..8].copy_from_slice(&(RECORD_SIZE as u16).to_le_bytes());
+    header
+}
+
+pub fn check_header(header: &[u8; HEADER_SIZE]) -> Result<(), BinaryError> {
+    if header[0..4] != MAGIC {
+        return Err(BinaryError::BadMagic);
+    }
+    let version = u16::from_le_bytes([header[4], header[5]]);
+    let record_size = u16::from_le_bytes([header[6], header[7]]);
+    if version != VERSION || record_size as usize != RECORD_SIZE {
+        return Err(BinaryError::UnsupportedVersion(version, record_size));
+    }
+    Ok(())
+}
+
+/// Tag stored for each transaction type. Tags are never reused.
+pub fn type_tag(t_type: Type) -> u8 {
+    match t_type {
+        Type::Deposit => 1,
+        Type::Withdrawal => 2,
+        Type::Dispute => 3,
+        Type::Resolve => 4,
+        Type::Chargeback => 5,
+        Type::ReverseChargeback => 6,
+        Type::CreditLimit => 7,
+    }
+}
+
+fn type_from_tag(tag: u8) -> Result<Type, BinaryError> {
+    Ok(match tag {
+        1 => Type::Deposit,
+        2 => Type::Withdrawal,
+        3 => Type::Dispute,
+        4 => Type::Resolve,
+        5 => Type::Chargeback,
+        6 => Type::ReverseChargeback,
+        7 => Type::CreditLimit,
+        tag => return Err(BinaryError::UnknownType(tag)),
+    })
+}
+
+pub fn encode(transaction: &Transaction) -> Result<[u8; RECORD_SIZE], BinaryError> {
+    let transaction_id = transaction.transaction_id;
+    if transaction.timestamp.is_some() {
+        return Err(BinaryError::TimestampNotSupported(transaction_id));
+    }
+    let (flags, scaled) = match transaction.amount {
+        Some(amount) => {
+            let amount = validate_amount(amount)
+                .map_err(|reason| BinaryError::InvalidAmount(transaction_id, reason))?;
+            let mut scaled = amount;
+            scaled.rescale(AMOUNT_SCALE);
+            let scaled = i64::try_from(scaled.mantissa())
+                .map_err(|_| BinaryError::InvalidAmount(transaction_id, "amount out of range"))?;
+            (HAS_AMOUNT, scaled)
+        }
+        None => (0, 0),
+    };
+
+    let mut record = [0; RECORD_SIZE];
+    record[0] = type_tag(transaction.t_type);
+    record[1] = flags;
+    record[2..4].copy_from_slice(&transaction.t_client_id.to_le_bytes());
+    record[4..8].copy_from_slice(&transaction_id.to_le_bytes());
+    record[8..16].copy_from_slice(&scaled.to_le_bytes());
+    Ok(record)
+}
+
+pub fn decode(record: &[u8; RECORD_SIZE]) -> Result<Transaction, BinaryError> {
+    let transaction_id = u32::from_le_bytes(record[4..8].try_into().unwrap());
+    let amount = if record[1] & HAS_AMOUNT != 0 {
+        let scaled = i64::from_le_bytes(record[8..16].try_into().unwrap());
+        let amount = validate_amount(Decimal::new(scaled, AMOUNT_SCALE))
+            .map_err(|reason| BinaryError::InvalidAmount(transaction_id, reason))?;
+        Some(amount)
+    } else {
+        None
+    };
+
+    Ok(Transaction {
+        t_type: type_from_tag(record[0])?,
+        t_client_id: u16::from_le_bytes([record[2], record[3]]),
+        transaction_id,
+        amount,
+        timestamp: None,
+    })
+}
+
+#[cfg(test)]
+pub mod tests {
+    use rust_decimal::dec;
+
+    use super::*;
+
+    #[test]
+    fn record_layout() {
+        let record = encode(&Transaction {
+            t_type: Type::Withdrawal,
+            t_client_id: 0x0102,
+            transaction_id: 0x03040506,
+            amount: Some(dec!(1.5)),
+            timestamp: None,
+        })
+        .unwrap();
+
+        assert_eq!(
+            record,
+            [
+                2, 1, 0x02, 0x01, 0x06, 0x05, 0x04, 0x03, 0x98, 0x3a, 0, 0, 0, 0, 0, 0
+            ]
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let transactions = [
+            Transaction {
+                t_type: Type::Deposit,
+                t_client_id: u16::MAX,
+                transaction_id: u32::MAX,
+                amount: Some(dec!(922337203685477.5807)),
+                timestamp: None,
+            },
+            Transaction {
+                t_type: Type::Dispute,
+                t_client_id: 1,
+                transaction_id: 2,
+                amount: None,
+                timestamp: None,
+            },
+        ];
+        for transaction in transactions {
+            assert_eq!(decode(&encode(&transaction).unwrap()).unwrap(), transaction);
+        }
+    }
+
+    #[test]
+    fn same_validation_as_csv() {
+        let transaction = |amount| Transaction {
+            t_type: Type::Deposit,
+            t_client_id: 1,
+            transaction_id: 7,
+            amount: Some(amount),
+            timestamp: None,
+        };
+
+        assert!(matches!(
+            encode(&transaction(dec!(-1))),
+            Err(BinaryError::InvalidAmount(7, "amount must be non-negative"))
+        ));
+        assert!(matches!(
+            encode(&transaction(dec!(1.00001))),
+            Err(BinaryError::InvalidAmount(
+                7,
+                "amount must be up to four decimals"
+            ))
+        ));
+        assert!(matches!(
+            encode(&transaction(dec!(922337203685477.5808))),
+            Err(BinaryError::InvalidAmount(7, "amount out of range"))
+        ));
+
+        let mut record = encode(&transaction(dec!(1))).unwrap();
+        record[8..16].copy_from_slice(&(-10_000i64).to_le_bytes());
+        assert!(matches!(
+            decode(&record),
+            Err(BinaryError::InvalidAmount(7, "amount must be non-negative"))
+        ));
+        record[0] = 42;
+        record[8..16].copy_from_slice(&0i64.to_le_bytes());
+        assert!(matches!(decode(&record), Err(BinaryError::UnknownType(42))));
+    }
+
+    #[test]
+    fn header_checks() {
+        assert!(check_header(&header()).is_ok());
+        assert!(matches!(
+            check_header(b"type,cli"),
+            Err(BinaryError::BadMagic)
+        ));
+        let mut future = header();
+        future[4] = 2;
+        assert!(matches!(
+            check_header(&future),
+            Err(BinaryError::UnsupportedVersion(2, 16))
+        ));
+    }
+}
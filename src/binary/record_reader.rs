@@ -0,0 +1,160 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::binary::error::BinaryError;
+use crate::binary::record::{HEADER_SIZE, RECORD_SIZE, check_header, decode};
+use crate::transaction::Transaction;
+
+/// Reads transactions from the binary format, checking the header first.
+pub struct BinaryReader<R: Read> {
+    reader: R,
+    /// Offset of the next record.
+    byte: u64,
+    /// Records read so far.
+    record: u64,
+}
+
+impl<R: Read> BinaryReader<R> {
+    pub fn new(mut reader: R) -> Result<Self, BinaryError> {
+        let mut header = [0; HEADER_SIZE];
+        reader.read_exact(&mut header)?;
+        check_header(&header)?;
+        Ok(Self {
+            reader,
+            byte: HEADER_SIZE as u64,
+            record: 0,
+        })
+    }
+
+    /// Offset of the next record.
+    pub fn byte(&self) -> u64 {
+        self.byte
+    }
+
+    /// Records read so far.
+    pub fn record(&self) -> u64 {
+        self.record
+    }
+
+    /// Reads the next record, or `None` at the end of the input. A truncated last record
+    /// is an error.
+    pub fn read(&mut self) -> Option<Result<Transaction, BinaryError>> {
+        let mut record = [0; RECORD_SIZE];
+        let mut filled = 0;
+        while filled < RECORD_SIZE {
+            match self.reader.read(&mut record[filled..]) {
+                Ok(0) if filled == 0 => return None,
+                Ok(0) => {
+                    return Some(Err(
+                        std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into()
+                    ));
+                }
+                Ok(read) => filled += read,
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(err) => return Some(Err(err.into())),
+            }
+        }
+        self.byte += RECORD_SIZE as u64;
+        self.record += 1;
+        Some(decode(&record))
+    }
+}
+
+impl<R: Read + Seek> BinaryReader<R> {
+    /// Continues at `byte`, an offset previously returned by `byte()`.
+    pub fn seek(&mut self, byte: u64) -> Result<(), BinaryError> {
+        let records = byte.saturating_sub(HEADER_SIZE as u64) / RECORD_SIZE as u64;
+        let byte = HEADER_SIZE as u64 + records * RECORD_SIZE as u64;
+        self.reader.seek(SeekFrom::Start(byte))?;
+        self.byte = byte;
+        self.record = records;
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for BinaryReader<R> {
+    type Item = Result<Transaction, BinaryError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::io::Cursor;
+
+    use rust_decimal::dec;
+
+    use super::*;
+    use crate::binary::record::encode;
+    use crate::binary::record_writer::BinaryWriter;
+    use crate::transaction::Type;
+
+    fn deposit(transaction_id: u32) -> Transaction {
+        Transaction {
+            t_type: Type::Deposit,
+            t_client_id: 1,
+            transaction_id,
+            amount: Some(dec!(2.5)),
+            timestamp: None,
+        }
+    }
+
+    fn file(transactions: &[Transaction]) -> Vec<u8> {
+        let mut writer = BinaryWriter::new(Vec::new()).unwrap();
+        for transaction in transactions {
+            writer.write(transaction).unwrap();
+        }
+        writer.into_inner().unwrap()
+    }
+
+    #[test]
+    fn read_records() {
+        let bytes = file(&[deposit(1), deposit(2)]);
+        let mut reader = BinaryReader::new(&bytes[..]).unwrap();
+
+        assert_eq!(reader.read().unwrap().unwrap(), deposit(1));
+        assert_eq!(reader.byte(), 24);
+        assert_eq!(reader.record(), 1);
+        assert_eq!(reader.read().unwrap().unwrap(), deposit(2));
+        assert!(reader.read().is_none());
+    }
+
+    #[test]
+    fn bad_records() {
+        let mut bytes = file(&[deposit(1)]);
+        // Unknown type tag: only this record is refused
+        let mut bad = encode(&deposit(2)).unwrap();
+        bad[0] = 99;
+        bytes.extend(bad);
+        bytes.extend(encode(&deposit(3)).unwrap());
+        // Truncated last record
+        bytes.extend(&encode(&deposit(4)).unwrap()[..5]);
+
+        let results: Vec<_> = BinaryReader::new(&bytes[..]).unwrap().collect();
+        assert_eq!(results.len(), 4);
+        assert!(results[0].is_ok());
+        assert!(matches!(results[1], Err(BinaryError::UnknownType(99))));
+        assert!(results[2].is_ok());
+        assert!(matches!(&results[3], Err(err) if err.is_fatal()));
+
+        assert!(matches!(
+            BinaryReader::new(&b"type,client,tx,amount\n"[..]),
+            Err(BinaryError::BadMagic)
+        ));
+    }
+
+    #[test]
+    fn seek_to_offset() {
+        let bytes = file(&[deposit(1), deposit(2), deposit(3)]);
+        let mut reader = BinaryReader::new(Cursor::new(&bytes)).unwrap();
+        reader.read();
+        let byte = reader.byte();
+
+        let mut reader = BinaryReader::new(Cursor::new(&bytes)).unwrap();
+        reader.seek(byte).unwrap();
+        assert_eq!(reader.record(), 1);
+        let rest: Vec<Transaction> = reader.map(Result::unwrap).collect();
+        assert_eq!(rest, vec![deposit(2), deposit(3)]);
+    }
+}
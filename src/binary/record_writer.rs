@@ -0,0 +1,34 @@
+use std::io::Write;
+
+use crate::binary::error::BinaryError;
+use crate::binary::record::{encode, header};
+use crate::transaction::Transaction;
+
+/// Writes transactions in the binary format, starting with the header.
+pub struct BinaryWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> BinaryWriter<W> {
+    pub fn new(mut writer: W) -> Result<Self, BinaryError> {
+        writer.write_all(&header())?;
+        Ok(Self { writer })
+    }
+
+    /// Appends a record. Nothing is written if the row can not be encoded.
+    pub fn write(&mut self, transaction: &Transaction) -> Result<(), BinaryError> {
+        let record = encode(transaction)?;
+        self.writer.write_all(&record)?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), BinaryError> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    pub fn into_inner(mut self) -> Result<W, BinaryError> {
+        self.flush()?;
+        Ok(self.writer)
+    }
+}
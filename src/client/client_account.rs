@@ -1,20 +1,39 @@
 use crate::{client::error::ClientAccountError, types::Amount};
 use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClientAccount {
     available: Amount,
     held: Amount,
     total: Amount,
     locked: bool,
+    credit_limit: Amount,
+    /// Transactions with a charged back portion not reversed yet. A reversal only
+    /// unlocks the account once none is left.
+    #[serde(default)]
+    chargebacks: u32,
+}
+
+impl Default for ClientAccount {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ClientAccount {
     pub fn new() -> Self {
+        Self::with_credit_limit(Decimal::ZERO)
+    }
+
+    pub fn with_credit_limit(credit_limit: Amount) -> Self {
         Self {
             available: Decimal::ZERO,
             held: Decimal::ZERO,
             total: Decimal::ZERO,
             locked: false,
+            credit_limit,
+            chargebacks: 0,
         }
     }
 
@@ -34,30 +53,69 @@ impl ClientAccount {
         self.locked
     }
 
+    /// How far available funds may go below zero through withdrawals.
+    pub fn credit_limit(&self) -> Amount {
+        self.credit_limit
+    }
+
+    /// Whether available funds are below the credit limit, e.g. after disputes or fees.
+    pub fn over_limit(&self) -> bool {
+        self.available < -self.credit_limit
+    }
+
+    pub fn set_credit_limit(&mut self, credit_limit: Amount) -> Result<(), ClientAccountError> {
+        if credit_limit < Decimal::ZERO {
+            return Err(ClientAccountError::NegativeAmount);
+        }
+
+        self.credit_limit = credit_limit;
+        Ok(())
+    }
+
     pub fn deposit(&mut self, amount: Amount) -> Result<(), ClientAccountError> {
+        self.deposit_with_fee(amount, Decimal::ZERO)
+    }
+
+    /// Deposits `amount` and charges `fee` as a separate movement. Both are checked
+    /// before the account changes.
+    pub fn deposit_with_fee(
+        &mut self,
+        amount: Amount,
+        fee: Amount,
+    ) -> Result<(), ClientAccountError> {
         if self.locked {
             return Err(ClientAccountError::Locked);
         }
 
-        if amount < Decimal::ZERO {
+        if amount < Decimal::ZERO || fee < Decimal::ZERO {
             return Err(ClientAccountError::NegativeAmount);
         }
 
         self.available += amount;
         self.total += amount;
-        Ok(())
+        self.charge_fee(fee)
     }
 
     pub fn withdrawal(&mut self, amount: Amount) -> Result<(), ClientAccountError> {
+        self.withdrawal_with_fee(amount, Decimal::ZERO)
+    }
+
+    /// Withdraws `amount` and charges `fee` as a separate movement. Available funds
+    /// must cover both.
+    pub fn withdrawal_with_fee(
+        &mut self,
+        amount: Amount,
+        fee: Amount,
+    ) -> Result<(), ClientAccountError> {
         if self.locked {
             return Err(ClientAccountError::Locked);
         }
 
-        if amount < Decimal::ZERO {
+        if amount < Decimal::ZERO || fee < Decimal::ZERO {
             return Err(ClientAccountError::NegativeAmount);
         }
 
-        if self.available > amount {
+        if self.available + self.credit_limit >= amount + fee {
             // meaning susfficient or equal amount of money
             self.available -= amount;
             self.total -= amount;
@@ -65,6 +123,18 @@ impl ClientAccount {
             return Err(ClientAccountError::InsufficientBalance);
         }
 
+        self.charge_fee(fee)
+    }
+
+    /// Debits a fee. Fees follow their triggering transaction, so they are charged on
+    /// locked accounts and may take available funds below zero.
+    pub fn charge_fee(&mut self, fee: Amount) -> Result<(), ClientAccountError> {
+        if fee < Decimal::ZERO {
+            return Err(ClientAccountError::NegativeAmount);
+        }
+
+        self.available -= fee;
+        self.total -= fee;
         Ok(())
     }
 
@@ -95,6 +165,28 @@ impl ClientAccount {
         self.locked = true; //  If a chargeback occurs the client's account should be immediately frozen
         Ok(())
     }
+
+    /// Counts a transaction getting its first charged back portion.
+    pub fn add_standing_chargeback(&mut self) {
+        self.chargebacks += 1;
+    }
+
+    /// Gives back the charged back portion of one transaction. With `unlock`, the account
+    /// is unlocked unless other transactions are still charged back.
+    pub fn reverse_chargeback(
+        &mut self,
+        amount: Amount,
+        unlock: bool,
+    ) -> Result<(), ClientAccountError> {
+        // A won representment gives back the charged back funds, even on a locked account
+        self.available += amount;
+        self.total += amount;
+        self.chargebacks = self.chargebacks.saturating_sub(1);
+        if unlock && self.chargebacks == 0 {
+            self.locked = false;
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -130,6 +222,8 @@ pub mod tests {
             held: Decimal::ZERO,
             total: Decimal::ZERO,
             locked: true,
+            credit_limit: Decimal::ZERO,
+            chargebacks: 0,
         };
 
         assert_eq!(
@@ -175,6 +269,8 @@ pub mod tests {
             held: Decimal::ZERO,
             total: Decimal::ZERO,
             locked: true,
+            credit_limit: Decimal::ZERO,
+            chargebacks: 0,
         };
 
         assert_eq!(
@@ -192,6 +288,113 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn client_withdrawal_with_fee() {
+        let mut client = ClientAccount::new();
+
+        assert!(client.deposit(dec!(10.0000)).is_ok());
+
+        assert!(
+            client
+                .withdrawal_with_fee(dec!(5.0000), dec!(0.5000))
+                .is_ok()
+        );
+        assert_eq!(client.available(), dec!(4.5000));
+        assert_eq!(client.total(), dec!(4.5000));
+
+        // 4.0001 + 0.5000 is more than available
+        assert_eq!(
+            client
+                .withdrawal_with_fee(dec!(4.0001), dec!(0.5000))
+                .unwrap_err(),
+            ClientAccountError::InsufficientBalance
+        );
+        assert_eq!(client.available(), dec!(4.5000));
+        // 4.0000 + 0.5000 empties it exactly
+        assert!(
+            client
+                .withdrawal_with_fee(dec!(4.0000), dec!(0.5000))
+                .is_ok()
+        );
+        assert_eq!(client.available(), dec!(0.0000));
+        assert_eq!(
+            client
+                .withdrawal_with_fee(dec!(1.0000), dec!(-0.5000))
+                .unwrap_err(),
+            ClientAccountError::NegativeAmount
+        );
+    }
+
+    #[test]
+    fn client_deposit_with_fee() {
+        let mut client = ClientAccount::new();
+
+        assert!(client.deposit_with_fee(dec!(10.0000), dec!(0.5000)).is_ok());
+        assert_eq!(client.available(), dec!(9.5000));
+        assert_eq!(client.total(), dec!(9.5000));
+
+        // A refused fee leaves the deposit out too
+        assert_eq!(
+            client
+                .deposit_with_fee(dec!(10.0000), dec!(-0.5000))
+                .unwrap_err(),
+            ClientAccountError::NegativeAmount
+        );
+        assert_eq!(client.available(), dec!(9.5000));
+        assert_eq!(client.total(), dec!(9.5000));
+    }
+
+    #[test]
+    fn client_charge_fee() {
+        let mut client = ClientAccount::new();
+
+        assert!(client.deposit(dec!(1.0000)).is_ok());
+        assert!(client.dispute(dec!(1.0000)).is_ok());
+        assert!(client.chargeback(dec!(1.0000)).is_ok());
+
+        assert!(client.charge_fee(dec!(15.0000)).is_ok());
+        assert_eq!(client.available(), dec!(-15.0000));
+        assert_eq!(client.total(), dec!(-15.0000));
+        assert!(client.locked());
+
+        assert_eq!(
+            client.charge_fee(dec!(-1)).unwrap_err(),
+            ClientAccountError::NegativeAmount
+        );
+    }
+
+    #[test]
+    fn client_withdrawal_credit_limit() {
+        let mut client = ClientAccount::with_credit_limit(dec!(10.0000));
+
+        assert!(client.deposit(dec!(5.0000)).is_ok());
+        assert!(client.withdrawal(dec!(14.0000)).is_ok());
+        assert_eq!(client.available(), dec!(-9.0000));
+        assert_eq!(client.total(), dec!(-9.0000));
+        assert!(!client.over_limit());
+
+        assert_eq!(
+            client.withdrawal(dec!(1.0001)).unwrap_err(),
+            ClientAccountError::InsufficientBalance
+        );
+        // Exactly at the limit is allowed
+        assert!(client.withdrawal(dec!(1.0000)).is_ok());
+        assert_eq!(client.available(), dec!(-10.0000));
+        assert!(!client.over_limit());
+        assert_eq!(
+            client.withdrawal(dec!(0.0001)).unwrap_err(),
+            ClientAccountError::InsufficientBalance
+        );
+
+        assert!(client.set_credit_limit(dec!(5.0000)).is_ok());
+        assert_eq!(client.credit_limit(), dec!(5.0000));
+        assert!(client.over_limit());
+        assert_eq!(
+            client.set_credit_limit(dec!(-1)).unwrap_err(),
+            ClientAccountError::NegativeAmount
+        );
+    }
+
     #[test]
     fn client_dispute() {
         let mut client = ClientAccount::new();
@@ -218,6 +421,8 @@ pub mod tests {
             held: Decimal::ZERO,
             total: Decimal::ZERO,
             locked: true,
+            credit_limit: Decimal::ZERO,
+            chargebacks: 0,
         };
 
         assert_eq!(
@@ -258,6 +463,8 @@ pub mod tests {
             held: Decimal::ZERO,
             total: Decimal::ZERO,
             locked: true,
+            credit_limit: Decimal::ZERO,
+            chargebacks: 0,
         };
 
         assert_eq!(
@@ -290,4 +497,23 @@ pub mod tests {
         assert_eq!(client.total(), dec!(0.0001));
         assert!(client.locked());
     }
+
+    #[test]
+    fn client_reverse_chargeback() {
+        let mut client = ClientAccount::new();
+
+        assert!(client.deposit(dec!(1.5555)).is_ok());
+        assert!(client.dispute(dec!(1.5555)).is_ok());
+        assert!(client.chargeback(dec!(1.5555)).is_ok());
+        assert!(client.locked());
+
+        assert!(client.reverse_chargeback(dec!(1.5555), false).is_ok());
+        assert_eq!(client.available(), dec!(1.5555));
+        assert_eq!(client.held(), dec!(0.0000));
+        assert_eq!(client.total(), dec!(1.5555));
+        assert!(client.locked());
+
+        assert!(client.reverse_chargeback(dec!(0.0000), true).is_ok());
+        assert!(!client.locked());
+    }
 }
@@ -0,0 +1,31 @@
+use arrow_schema::{ArrowError, DataType};
+use parquet::errors::ParquetError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ColumnarError {
+    #[error("Error accessing Parquet file: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Error accessing Parquet file: {0}")]
+    Parquet(#[from] ParquetError),
+
+    #[error("Error converting Parquet columns: {0}")]
+    Arrow(#[from] ArrowError),
+
+    #[error("Parquet input has no {0} column")]
+    MissingColumn(&'static str),
+
+    #[error("Parquet column {0} can not be read from {1}")]
+    UnsupportedColumn(&'static str, DataType),
+
+    #[error("Invalid Parquet row {0}: {1}")]
+    InvalidRow(u64, String),
+}
+
+impl ColumnarError {
+    /// Whether the rest of the input can not be read anymore, as opposed to one bad row.
+    pub fn is_fatal(&self) -> bool {
+        !matches!(self, ColumnarError::InvalidRow(..))
+    }
+}
@@ -0,0 +1,11 @@
+//! Apache Parquet input and output, for transactions kept by analytics tooling.
+//!
+//! Inputs need the `type`, `client` and `tx` columns, and may have `amount` and
+//! `timestamp`. Any column type Arrow can cast to the expected one is accepted, e.g.
+//! dictionary encoded or large strings for `type` and any integer for `client`.
+//! Amounts may be decimal, integer, floating point or string columns.
+
+pub mod error;
+pub mod parquet_reader;
+pub mod parquet_writer;
+pub mod transaction_batch;
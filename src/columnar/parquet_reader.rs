@@ -0,0 +1,79 @@
+use std::io::Read;
+use std::path::Path;
+
+use parquet::arrow::arrow_reader::{ParquetRecordBatchReader, ParquetRecordBatchReaderBuilder};
+
+use crate::columnar::error::ColumnarError;
+use crate::columnar::transaction_batch::transactions_from_batch;
+use crate::transaction::Transaction;
+
+/// Bytes every Parquet file starts and ends with.
+pub const PARQUET_MAGIC: &[u8] = b"PAR1";
+/// Rows decoded at once.
+pub const BATCH_ROWS: usize = 8192;
+
+/// Whether `path` is a Parquet file, by its `.parquet` extension or else its magic bytes.
+pub fn is_parquet(path: impl AsRef<Path>) -> bool {
+    let path = path.as_ref();
+    if path
+        .extension()
+        .is_some_and(|extension| extension == "parquet")
+    {
+        return true;
+    }
+    let mut head = [0; 4];
+    std::fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut head))
+        .is_ok_and(|_| head == PARQUET_MAGIC)
+}
+
+/// Transactions of a Parquet file, decoded one record batch at a time. Parquet keeps
+/// its metadata at the end of the file, so only regular files can be read.
+pub struct ParquetTransactions {
+    batches: ParquetRecordBatchReader,
+    rows: std::vec::IntoIter<Result<Transaction, ColumnarError>>,
+    /// Number of the first row of the next batch.
+    next_row: u64,
+}
+
+impl ParquetTransactions {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, ColumnarError> {
+        let file = std::fs::File::open(path)?;
+        let batches = ParquetRecordBatchReaderBuilder::try_new(file)?
+            .with_batch_size(BATCH_ROWS)
+            .build()?;
+        Ok(Self {
+            batches,
+            rows: Vec::new().into_iter(),
+            next_row: 0,
+        })
+    }
+
+    /// Rows read so far.
+    pub fn rows_read(&self) -> u64 {
+        self.next_row - self.rows.len() as u64
+    }
+}
+
+impl Iterator for ParquetTransactions {
+    type Item = Result<Transaction, ColumnarError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(row) = self.rows.next() {
+                return Some(row);
+            }
+            let batch = match self.batches.next()? {
+                Ok(batch) => batch,
+                Err(err) => return Some(Err(err.into())),
+            };
+            match transactions_from_batch(&batch, self.next_row) {
+                Ok(rows) => {
+                    self.next_row += rows.len() as u64;
+                    self.rows = rows.into_iter();
+                }
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+use arrow_array::builder::{StringBuilder, UInt16Builder, UInt32Builder, UInt64Builder};
+use arrow_array::{ArrayRef, BooleanArray, RecordBatch, UInt16Array};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::file::properties::WriterProperties;
+
+use crate::client::client_account::ClientAccount;
+use crate::columnar::error::ColumnarError;
+use crate::columnar::parquet_reader::BATCH_ROWS;
+use crate::columnar::transaction_batch::{AMOUNT_PRECISION, amounts_to_column};
+use crate::transaction::{AMOUNT_SCALE, Transaction};
+use crate::types::{Amount, ClientId};
+
+fn amount_type() -> DataType {
+    DataType::Decimal128(AMOUNT_PRECISION, AMOUNT_SCALE as i8)
+}
+
+fn properties() -> WriterProperties {
+    WriterProperties::builder()
+        .set_compression(Compression::ZSTD(ZstdLevel::default()))
+        .build()
+}
+
+/// Columns of the balances, the same as the CSV output.
+pub fn balances_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("client", DataType::UInt16, false),
+        Field::new("available", amount_type(), false),
+        Field::new("held", amount_type(), false),
+        Field::new("total", amount_type(), false),
+        Field::new("locked", DataType::Boolean, false),
+    ]))
+}
+
+/// Writes the balances of `accounts` as a Parquet file.
+pub fn write_balances<W: Write + Send>(
+    writer: W,
+    accounts: &[(ClientId, ClientAccount)],
+) -> Result<W, ColumnarError> {
+    let amounts = |amount: fn(&ClientAccount) -> Amount| {
+        amounts_to_column(accounts.iter().map(|(_, account)| Some(amount(account))))
+            .map(|column| Arc::new(column) as ArrayRef)
+    };
+    let batch = RecordBatch::try_new(
+        balances_schema(),
+        vec![
+            Arc::new(UInt16Array::from_iter_values(
+                accounts.iter().map(|(client_id, _)| *client_id),
+            )),
+            amounts(ClientAccount::available)?,
+            amounts(ClientAccount::held)?,
+            amounts(ClientAccount::total)?,
+            Arc::new(BooleanArray::from_iter(
+                accounts.iter().map(|(_, account)| Some(account.locked())),
+            )),
+        ],
+    )?;
+
+    let mut writer = ArrowWriter::try_new(writer, balances_schema(), Some(properties()))?;
+    writer.write(&batch)?;
+    Ok(writer.into_inner()?)
+}
+
+/// Columns of the outcome log: each row as read and what the engine made of it.
+pub fn outcomes_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("type", DataType::Utf8, false),
+        Field::new("client", DataType::UInt16, false),
+        Field::new("tx", DataType::UInt32, false),
+        Field::new("amount", amount_type(), true),
+        Field::new("timestamp", DataType::UInt64, true),
+        Field::new("outcome", DataType::Utf8, false),
+    ]))
+}
+
+/// Per-transaction outcome log. Rows are gathered into column builders and written a
+/// record batch at a time; `close` must be called to complete the file.
+pub struct OutcomeWriter<W: Write + Send> {
+    writer: ArrowWriter<W>,
+    types: StringBuilder,
+    clients: UInt16Builder,
+    transaction_ids: UInt32Builder,
+    amounts: Vec<Option<Amount>>,
+    timestamps: UInt64Builder,
+    outcomes: StringBuilder,
+}
+
+impl OutcomeWriter<std::fs::File> {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ColumnarError> {
+        Self::from_writer(std::fs::File::create(path)?)
+    }
+}
+
+impl<W: Write + Send> OutcomeWriter<W> {
+    pub fn from_writer(writer: W) -> Result<Self, ColumnarError> {
+        Ok(Self {
+            writer: ArrowWriter::try_new(writer, outcomes_schema(), Some(properties()))?,
+            types: StringBuilder::new(),
+            clients: UInt16Builder::new(),
+            transaction_ids: UInt32Builder::new(),
+            amounts: Vec::new(),
+            timestamps: UInt64Builder::new(),
+            outcomes: StringBuilder::new(),
+        })
+    }
+
+    pub fn write(&mut self, transaction: &Transaction, outcome: &str) -> Result<(), ColumnarError> {
+        self.types.append_value(transaction.t_type.name());
+        self.clients.append_value(transaction.t_client_id);
+        self.transaction_ids
+            .append_value(transaction.transaction_id);
+        self.amounts.push(transaction.amount);
+        self.timestamps.append_option(transaction.timestamp);
+        self.outcomes.append_value(outcome);
+        if self.amounts.len() >= BATCH_ROWS {
+            self.write_batch()?;
+        }
+        Ok(())
+    }
+
+    fn write_batch(&mut self) -> Result<(), ColumnarError> {
+        if self.amounts.is_empty() {
+            return Ok(());
+        }
+        let amounts = amounts_to_column(self.amounts.drain(..))?;
+        let batch = RecordBatch::try_new(
+            outcomes_schema(),
+            vec![
+                Arc::new(self.types.finish()),
+                Arc::new(self.clients.finish()),
+                Arc::new(self.transaction_ids.finish()),
+                Arc::new(amounts),
+                Arc::new(self.timestamps.finish()),
+                Arc::new(self.outcomes.finish()),
+            ],
+        )?;
+        self.writer.write(&batch)?;
+        Ok(())
+    }
+
+    /// Writes the remaining rows and the file footer.
+    pub fn close(mut self) -> Result<W, ColumnarError> {
+        self.write_batch()?;
+        Ok(self.writer.into_inner()?)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::{Decimal128Type, UInt16Type};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use rust_decimal::dec;
+
+    use super::*;
+    use crate::columnar::transaction_batch::transactions_from_batch;
+    use crate::transaction::Type;
+
+    fn read(name: &str, bytes: Vec<u8>) -> Vec<RecordBatch> {
+        let path = std::env::temp_dir().join(format!("{}_{}.parquet", name, std::process::id()));
+        std::fs::write(&path, bytes).unwrap();
+        let batches = ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(&path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        std::fs::remove_file(&path).unwrap();
+        batches
+    }
+
+    #[test]
+    fn balances() {
+        let mut account = ClientAccount::new();
+        account.deposit(dec!(2.5)).unwrap();
+        let bytes = write_balances(Vec::new(), &[(7, account)]).unwrap();
+
+        let batches = read("balances", bytes);
+        let batch = &batches[0];
+        assert_eq!(batch.schema(), balances_schema());
+        assert_eq!(batch.column(0).as_primitive::<UInt16Type>().value(0), 7);
+        // 2.5 with four decimals
+        assert_eq!(
+            batch.column(1).as_primitive::<Decimal128Type>().value(0),
+            25_000
+        );
+        assert!(!batch.column(4).as_boolean().value(0));
+    }
+
+    #[test]
+    fn outcomes_read_back_as_transactions() {
+        let transaction = Transaction {
+            t_type: Type::Withdrawal,
+            t_client_id: 1,
+            transaction_id: 2,
+            amount: Some(dec!(1.2345)),
+            timestamp: Some(1_700_000_000),
+        };
+        let mut writer = OutcomeWriter::from_writer(Vec::new()).unwrap();
+        for _ in 0..BATCH_ROWS + 1 {
+            writer.write(&transaction, "applied").unwrap();
+        }
+        let batches = read("outcomes", writer.close().unwrap());
+        assert_eq!(
+            batches.iter().map(RecordBatch::num_rows).sum::<usize>(),
+            BATCH_ROWS + 1
+        );
+
+        let rows = transactions_from_batch(&batches[0], 0).unwrap();
+        assert_eq!(rows[0].as_ref().unwrap(), &transaction);
+        assert_eq!(batches[0].column(5).as_string::<i32>().value(0), "applied");
+    }
+}
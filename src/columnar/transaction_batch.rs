@@ -0,0 +1,241 @@
+use std::str::FromStr;
+
+use arrow_array::cast::AsArray;
+use arrow_array::types::{Decimal128Type, UInt16Type, UInt32Type, UInt64Type};
+use arrow_array::{Array, ArrayRef, Decimal128Array, RecordBatch};
+use arrow_schema::DataType;
+use rust_decimal::Decimal;
+
+use crate::columnar::error::ColumnarError;
+use crate::transaction::{AMOUNT_SCALE, Transaction, Type, validate_amount};
+use crate::types::Amount;
+
+/// Precision of the decimal columns written, the widest Arrow allows for 128 bits.
+pub const AMOUNT_PRECISION: u8 = 38;
+
+/// Maps a record batch onto transactions, converting each column as a whole before
+/// assembling the rows. `first_row` is the number of the batch's first row in the file.
+/// A row that can not be mapped is refused on its own.
+pub fn transactions_from_batch(
+    batch: &RecordBatch,
+    first_row: u64,
+) -> Result<Vec<Result<Transaction, ColumnarError>>, ColumnarError> {
+    let types = required_column(batch, "type", &DataType::Utf8)?;
+    let types = types.as_string::<i32>();
+    let clients = required_column(batch, "client", &DataType::UInt16)?;
+    let clients = clients.as_primitive::<UInt16Type>();
+    let transaction_ids = required_column(batch, "tx", &DataType::UInt32)?;
+    let transaction_ids = transaction_ids.as_primitive::<UInt32Type>();
+    let timestamps = column(batch, "timestamp", &DataType::UInt64)?;
+    let timestamps = timestamps
+        .as_ref()
+        .map(|timestamps| timestamps.as_primitive::<UInt64Type>());
+    let amounts = match batch.column_by_name("amount") {
+        Some(amounts) => amounts_from_column(amounts)?,
+        None => vec![Ok(None); batch.num_rows()],
+    };
+
+    Ok(amounts
+        .into_iter()
+        .enumerate()
+        .map(|(index, amount)| {
+            let invalid = |reason: &str| {
+                ColumnarError::InvalidRow(first_row + index as u64, reason.to_string())
+            };
+            let t_type = types
+                .is_valid(index)
+                .then(|| Type::from_name(types.value(index)))
+                .flatten()
+                .ok_or_else(|| invalid("unknown transaction type"))?;
+            let t_client_id = clients
+                .is_valid(index)
+                .then(|| clients.value(index))
+                .ok_or_else(|| invalid("missing or out of range client"))?;
+            let transaction_id = transaction_ids
+                .is_valid(index)
+                .then(|| transaction_ids.value(index))
+                .ok_or_else(|| invalid("missing or out of range tx"))?;
+            Ok(Transaction {
+                t_type,
+                t_client_id,
+                transaction_id,
+                amount: amount.map_err(invalid)?,
+                timestamp: timestamps
+                    .filter(|timestamps| timestamps.is_valid(index))
+                    .map(|timestamps| timestamps.value(index)),
+            })
+        })
+        .collect())
+}
+
+/// The `name` column cast to `data_type`. Values that do not fit become nulls.
+fn column(
+    batch: &RecordBatch,
+    name: &'static str,
+    data_type: &DataType,
+) -> Result<Option<ArrayRef>, ColumnarError> {
+    batch
+        .column_by_name(name)
+        .map(|column| {
+            arrow_cast::cast(column, data_type)
+                .map_err(|_| ColumnarError::UnsupportedColumn(name, column.data_type().clone()))
+        })
+        .transpose()
+}
+
+fn required_column(
+    batch: &RecordBatch,
+    name: &'static str,
+    data_type: &DataType,
+) -> Result<ArrayRef, ColumnarError> {
+    column(batch, name, data_type)?.ok_or(ColumnarError::MissingColumn(name))
+}
+
+/// Amounts of a column, checked with the same rules as CSV amounts. The scale of a
+/// decimal column is part of its type, so trailing zeros beyond four decimals are
+/// dropped before checking.
+fn amounts_from_column(
+    amounts: &ArrayRef,
+) -> Result<Vec<Result<Option<Amount>, &'static str>>, ColumnarError> {
+    let unsupported = || ColumnarError::UnsupportedColumn("amount", amounts.data_type().clone());
+    match amounts.data_type() {
+        DataType::Decimal32(_, scale)
+        | DataType::Decimal64(_, scale)
+        | DataType::Decimal128(_, scale)
+        | DataType::Decimal256(_, scale) => {
+            let scale = u32::try_from(*scale).map_err(|_| unsupported())?;
+            let decimals = arrow_cast::cast(
+                amounts,
+                &DataType::Decimal128(AMOUNT_PRECISION, scale as i8),
+            )
+            .map_err(|_| unsupported())?;
+            Ok(decimals
+                .as_primitive::<Decimal128Type>()
+                .iter()
+                .map(|raw| {
+                    raw.map(|raw| {
+                        let amount = Decimal::try_from_i128_with_scale(raw, scale)
+                            .map_err(|_| "amount out of range")?;
+                        validate_amount(if scale > AMOUNT_SCALE {
+                            amount.normalize()
+                        } else {
+                            amount
+                        })
+                    })
+                    .transpose()
+                })
+                .collect())
+        }
+        _ => {
+            let strings = arrow_cast::cast(amounts, &DataType::Utf8).map_err(|_| unsupported())?;
+            Ok(strings
+                .as_string::<i32>()
+                .iter()
+                .map(|amount| {
+                    amount
+                        .map(|amount| {
+                            Decimal::from_str(amount)
+                                .map_err(|_| "invalid amount")
+                                .and_then(validate_amount)
+                        })
+                        .transpose()
+                })
+                .collect())
+        }
+    }
+}
+
+/// Amounts as a decimal column with four decimals.
+pub fn amounts_to_column(
+    amounts: impl IntoIterator<Item = Option<Amount>>,
+) -> Result<Decimal128Array, ColumnarError> {
+    Ok(amounts
+        .into_iter()
+        .map(|amount| {
+            amount.map(|mut amount| {
+                amount.rescale(AMOUNT_SCALE);
+                amount.mantissa()
+            })
+        })
+        .collect::<Decimal128Array>()
+        .with_precision_and_scale(AMOUNT_PRECISION, AMOUNT_SCALE as i8)?)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::sync::Arc;
+
+    use arrow_array::{Float64Array, Int64Array, StringArray, UInt32Array};
+    use rust_decimal::dec;
+
+    use super::*;
+
+    fn batch(amounts: ArrayRef) -> RecordBatch {
+        RecordBatch::try_from_iter([
+            (
+                "type",
+                Arc::new(StringArray::from(vec!["deposit", "withdrawal", "bogus"])) as ArrayRef,
+            ),
+            (
+                "client",
+                Arc::new(Int64Array::from(vec![1, 70_000, 3])) as ArrayRef,
+            ),
+            ("tx", Arc::new(UInt32Array::from(vec![1, 2, 3])) as ArrayRef),
+            ("amount", amounts),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn map_batch() {
+        let amounts = amounts_to_column([Some(dec!(1.5)), Some(dec!(2)), None]).unwrap();
+        let rows = transactions_from_batch(&batch(Arc::new(amounts)), 10).unwrap();
+
+        assert_eq!(
+            rows[0].as_ref().unwrap(),
+            &Transaction {
+                t_type: Type::Deposit,
+                t_client_id: 1,
+                transaction_id: 1,
+                amount: Some(dec!(1.5)),
+                timestamp: None,
+            }
+        );
+        // The client does not fit a u16
+        assert!(matches!(rows[1], Err(ColumnarError::InvalidRow(11, _))));
+        assert!(matches!(rows[2], Err(ColumnarError::InvalidRow(12, _))));
+    }
+
+    #[test]
+    fn same_validation_as_csv() {
+        let amounts = Float64Array::from(vec![Some(1.25), Some(-1.0), Some(0.00001)]);
+        let rows = transactions_from_batch(&batch(Arc::new(amounts)), 0).unwrap();
+        assert_eq!(rows[0].as_ref().unwrap().amount, Some(dec!(1.25)));
+        assert!(rows[1].is_err());
+
+        let amounts = StringArray::from(vec!["1.12345", "1.0", "x"]);
+        let rows = transactions_from_batch(&batch(Arc::new(amounts)), 0).unwrap();
+        assert!(rows[0].is_err());
+
+        // Six decimal column: only values with more than four significant decimals fail
+        let amounts = Decimal128Array::from(vec![1_500_000, 1_000_001, 0])
+            .with_precision_and_scale(10, 6)
+            .unwrap();
+        let rows = transactions_from_batch(&batch(Arc::new(amounts)), 0).unwrap();
+        assert_eq!(rows[0].as_ref().unwrap().amount, Some(dec!(1.5)));
+        assert!(rows[1].is_err());
+    }
+
+    #[test]
+    fn missing_column() {
+        let batch = RecordBatch::try_from_iter([(
+            "type",
+            Arc::new(StringArray::from(vec!["deposit"])) as ArrayRef,
+        )])
+        .unwrap();
+        assert!(matches!(
+            transactions_from_batch(&batch, 0),
+            Err(ColumnarError::MissingColumn("client"))
+        ));
+    }
+}
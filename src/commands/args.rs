@@ -0,0 +1,173 @@
+//! Arguments shared by several subcommands.
+
+use clap::{Arg, ArgAction, ArgMatches};
+
+use payments_engine::compression::Compression;
+use payments_engine::export::calendar::{parse_date, parse_end_of_date};
+use payments_engine::history::account_history::Period;
+
+pub fn config_arg() -> Arg {
+    Arg::new("config")
+        .display_order(2)
+        .long("config")
+        .help("Provide engine configuration file")
+        .action(ArgAction::Set)
+        .value_name("CONFIG.toml")
+        .value_parser(clap::builder::NonEmptyStringValueParser::new())
+}
+
+pub fn state_arg() -> Arg {
+    Arg::new("state")
+        .display_order(3)
+        .long("state")
+        .help("Load engine state from this file if it exists, and save it back when done")
+        .action(ArgAction::Set)
+        .value_name("STATE.json")
+        .value_parser(clap::builder::NonEmptyStringValueParser::new())
+}
+
+pub fn access_list_arg() -> Arg {
+    Arg::new("access_list")
+        .display_order(4)
+        .long("access-list")
+        .help("Provide client block/allow list file, reloaded on SIGHUP")
+        .action(ArgAction::Set)
+        .value_name("ACCESS_LIST.csv")
+        .value_parser(clap::builder::NonEmptyStringValueParser::new())
+}
+
+pub fn rejects_arg() -> Arg {
+    Arg::new("rejects")
+        .display_order(5)
+        .long("rejects")
+        .help("Write refused rows and the reason they were refused to this file")
+        .action(ArgAction::Set)
+        .value_name("REJECTS.csv")
+        .value_parser(clap::builder::NonEmptyStringValueParser::new())
+}
+
+pub fn dead_letter_arg() -> Arg {
+    Arg::new("dead_letter")
+        .display_order(6)
+        .long("dead-letter")
+        .help("Write rows refused for a transient reason to this file, in the input format")
+        .action(ArgAction::Set)
+        .value_name("DEAD_LETTER.csv")
+        .value_parser(clap::builder::NonEmptyStringValueParser::new())
+}
+
+pub fn checkpoint_every_arg() -> Arg {
+    Arg::new("checkpoint_every")
+        .display_order(7)
+        .long("checkpoint-every")
+        .help("With --state, save the state and input position every this many rows")
+        .action(ArgAction::Set)
+        .value_name("ROWS")
+        .value_parser(clap::value_parser!(u64).range(1..))
+        .default_value("100000")
+}
+
+pub fn resume_arg() -> Arg {
+    Arg::new("resume")
+        .display_order(8)
+        .long("resume")
+        .help("Continue the input from the checkpoint saved in --state")
+        .action(ArgAction::SetTrue)
+        .requires("state")
+}
+
+pub fn emit_every_arg() -> Arg {
+    Arg::new("emit_every")
+        .display_order(9)
+        .long("emit-every")
+        .help("Print the balances every this many seconds while reading, e.g. from a pipe")
+        .action(ArgAction::Set)
+        .value_name("SECONDS")
+        .value_parser(clap::value_parser!(u64).range(1..))
+}
+
+pub fn compress_output_arg() -> Arg {
+    Arg::new("compress_output")
+        .display_order(10)
+        .long("compress-output")
+        .help("Compress the balances written to stdout")
+        .action(ArgAction::Set)
+        .value_name("COMPRESSION")
+        .value_parser(["none", "gzip", "zstd"])
+        .default_value("none")
+}
+
+#[cfg(feature = "parquet")]
+pub fn parquet_output_arg() -> Arg {
+    Arg::new("parquet_output")
+        .display_order(11)
+        .long("parquet-output")
+        .help("Also write the final balances to this Parquet file")
+        .action(ArgAction::Set)
+        .value_name("BALANCES.parquet")
+        .value_parser(clap::builder::NonEmptyStringValueParser::new())
+}
+
+#[cfg(feature = "parquet")]
+pub fn outcomes_arg() -> Arg {
+    Arg::new("outcomes")
+        .display_order(12)
+        .long("outcomes")
+        .help("Write every row read and its outcome to this Parquet file")
+        .action(ArgAction::Set)
+        .value_name("OUTCOMES.parquet")
+        .value_parser(clap::builder::NonEmptyStringValueParser::new())
+}
+
+pub fn changes_arg() -> Arg {
+    Arg::new("changes")
+        .display_order(13)
+        .long("changes")
+        .help("Write every change to an account to this file, one JSON object per line")
+        .action(ArgAction::Set)
+        .value_name("CHANGES.jsonl")
+        .value_parser(clap::builder::NonEmptyStringValueParser::new())
+}
+
+pub fn output_compression(args: &ArgMatches) -> Compression {
+    Compression::from_name(args.get_one::<String>("compress_output").unwrap()).unwrap()
+}
+
+pub fn from_arg() -> Arg {
+    Arg::new("from")
+        .long("from")
+        .help("First day of the period, in UTC")
+        .action(ArgAction::Set)
+        .value_name("YYYY-MM-DD")
+}
+
+pub fn to_arg() -> Arg {
+    Arg::new("to")
+        .long("to")
+        .help("Last day of the period, in UTC")
+        .action(ArgAction::Set)
+        .value_name("YYYY-MM-DD")
+}
+
+/// The period given with `--from` / `--to`, whole days in UTC.
+pub fn period_from_args(args: &ArgMatches) -> Result<Period, Box<dyn std::error::Error>> {
+    Ok(Period {
+        from: args
+            .get_one::<String>("from")
+            .map(|date| parse_date(date))
+            .transpose()?,
+        to: args
+            .get_one::<String>("to")
+            .map(|date| parse_end_of_date(date))
+            .transpose()?,
+    })
+}
+
+pub fn transaction_id_arg() -> Arg {
+    Arg::new("tx")
+        .help("Transaction ID")
+        .action(ArgAction::Set)
+        .value_name("TX")
+        .value_parser(clap::value_parser!(u32))
+        .required(true)
+}
@@ -0,0 +1,76 @@
+use std::sync::Arc;
+
+use clap::ArgMatches;
+
+use payments_engine::compression::OutputFile;
+use payments_engine::engine::payments_engine::PaymentsEngine;
+use payments_engine::observe::jsonl_sink::JsonlSink;
+use payments_engine::snapshot::input_checkpoint::InputCheckpoint;
+#[cfg(feature = "webhooks")]
+use payments_engine::webhooks::outbox::Outbox;
+
+use crate::commands::refused_rows::RefusedRows;
+
+/// Bytes a checkpoint may write per input byte read since the previous one. Each save
+/// rewrites the whole state, which grows with the input, so saves get further apart as
+/// it grows and their I/O stays proportional to the input instead of its square.
+const CHECKPOINT_WRITE_RATIO: u64 = 16;
+
+/// Periodic saves of the engine state together with the input position, see `--resume`.
+#[derive(Clone)]
+pub struct Checkpoints {
+    pub state: String,
+    pub every: u64,
+    pub resume: bool,
+    /// Input offset before which no checkpoint is due, see `CHECKPOINT_WRITE_RATIO`.
+    pub next_byte: u64,
+    /// Changes of the rows a checkpoint covers are flushed with it.
+    pub changes: Option<Arc<JsonlSink<OutputFile>>>,
+    /// Webhooks of the rows a checkpoint covers are stored with it.
+    #[cfg(feature = "webhooks")]
+    pub outbox: Option<Arc<Outbox>>,
+}
+
+impl Checkpoints {
+    pub fn from_args(args: &ArgMatches) -> Option<Self> {
+        Some(Self {
+            state: args.get_one::<String>("state")?.clone(),
+            every: *args.get_one::<u64>("checkpoint_every").unwrap(),
+            resume: args.get_flag("resume"),
+            next_byte: 0,
+            changes: None,
+            #[cfg(feature = "webhooks")]
+            outbox: None,
+        })
+    }
+
+    pub fn is_due(&self, position: &csv::Position) -> bool {
+        position.record().is_multiple_of(self.every) && position.byte() >= self.next_byte
+    }
+
+    /// Saves the engine state with `position` as its input checkpoint. Refused rows,
+    /// changes and queued webhooks are written first: after a crash they may be reported
+    /// twice, but never lost.
+    pub async fn save(
+        &mut self,
+        payments_engine: &PaymentsEngine,
+        path: &str,
+        position: &csv::Position,
+        refused_rows: &RefusedRows,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        refused_rows.flush()?;
+        if let Some(changes) = &self.changes {
+            changes.flush()?;
+        }
+        #[cfg(feature = "webhooks")]
+        if let Some(outbox) = &self.outbox {
+            outbox.store_queued()?;
+        }
+        payments_engine
+            .set_input_checkpoint(InputCheckpoint::take(path, position)?)
+            .await;
+        let written = payments_engine.snapshot().await.save(&self.state)?;
+        self.next_byte = position.byte() + written / CHECKPOINT_WRITE_RATIO;
+        Ok(())
+    }
+}
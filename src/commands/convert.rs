@@ -0,0 +1,105 @@
+use std::cell::Cell;
+use std::io::{BufRead, BufReader};
+
+use clap::{Arg, ArgAction, ArgMatches, Command};
+
+use payments_engine::binary::record::MAGIC;
+use payments_engine::binary::record_reader::BinaryReader;
+use payments_engine::binary::record_writer::BinaryWriter;
+use payments_engine::compression::{OutputFile, create_output};
+use payments_engine::ingest::csv_reader::transactions_reader;
+use payments_engine::ingest::error::ReadError;
+use payments_engine::input::{Input, STDIN};
+use payments_engine::transaction::Transaction;
+
+pub fn command() -> Command {
+    Command::new("convert")
+        .about("Convert a transactions file between CSV and the binary format")
+        .arg(
+            Arg::new("input")
+                .help("Transactions file to convert, or - to read the standard input")
+                .action(ArgAction::Set)
+                .value_name("INPUT")
+                .value_parser(clap::builder::NonEmptyStringValueParser::new())
+                .required(true),
+        )
+        .arg(
+            Arg::new("output")
+                .help("File to write, or - to write the standard output")
+                .action(ArgAction::Set)
+                .value_name("OUTPUT")
+                .value_parser(clap::builder::NonEmptyStringValueParser::new())
+                .required(true),
+        )
+        .arg(
+            Arg::new("to")
+                .long("to")
+                .help("Output format, by default the one the input is not in")
+                .action(ArgAction::Set)
+                .value_name("FORMAT")
+                .value_parser(["csv", "binary"]),
+        )
+}
+
+/// Converts a transactions file between CSV and the binary format, by default to the
+/// format it is not in. Rows failing validation are reported and left out.
+pub fn run(args: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let input_path = args.get_one::<String>("input").unwrap();
+    let output_path = args.get_one::<String>("output").unwrap();
+
+    let mut input = BufReader::new(Input::open(input_path)?);
+    let binary_input = input.fill_buf()?.starts_with(&MAGIC);
+    let to_binary = match args.get_one::<String>("to").map(String::as_str) {
+        Some(format) => format == "binary",
+        None => !binary_input,
+    };
+    let output: OutputFile = if output_path == STDIN {
+        Box::new(std::io::stdout())
+    } else {
+        create_output(output_path, false)?.0
+    };
+
+    let transactions: Box<dyn Iterator<Item = Result<Transaction, ReadError>>> = if binary_input {
+        Box::new(BinaryReader::new(input)?.map(|row| row.map_err(ReadError::from)))
+    } else {
+        Box::new(
+            transactions_reader(input)
+                .into_deserialize()
+                .map(|row| row.map_err(ReadError::from)),
+        )
+    };
+    let refused = Cell::new(0u64);
+    let rows = transactions.filter_map(|row| match row {
+        Err(err) if !err.is_fatal() => {
+            eprintln!("Error deserializing transaction: {}", err);
+            refused.set(refused.get() + 1);
+            None
+        }
+        row => Some(row),
+    });
+
+    let mut converted = 0u64;
+    if to_binary {
+        let mut writer = BinaryWriter::new(output)?;
+        for transaction in rows {
+            match writer.write(&transaction?) {
+                Ok(()) => converted += 1,
+                Err(err) if err.is_fatal() => return Err(err.into()),
+                Err(err) => {
+                    eprintln!("Error converting transaction: {}", err);
+                    refused.set(refused.get() + 1);
+                }
+            }
+        }
+        writer.flush()?;
+    } else {
+        let mut writer = csv::Writer::from_writer(output);
+        for transaction in rows {
+            writer.serialize(transaction?)?;
+            converted += 1;
+        }
+        writer.flush()?;
+    }
+    eprintln!("{} rows converted, {} refused", converted, refused.get());
+    Ok(())
+}
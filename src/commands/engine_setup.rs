@@ -0,0 +1,86 @@
+//! Loading and saving the engine, and printing its balances.
+
+use std::io::Write;
+
+use clap::ArgMatches;
+
+use payments_engine::access::access_list::ClientAccessList;
+use payments_engine::compression::Compression;
+use payments_engine::config::engine_config::EngineConfig;
+use payments_engine::engine::error::EngineError;
+use payments_engine::engine::payments_engine::PaymentsEngine;
+use payments_engine::snapshot::engine_snapshot::EngineSnapshot;
+
+/// Builds the engine from `--config` and, when given, the `--state` snapshot.
+pub fn load_engine(args: &ArgMatches) -> Result<PaymentsEngine, Box<dyn std::error::Error>> {
+    let config = match args.get_one::<String>("config") {
+        Some(path) => EngineConfig::from_file(path)?,
+        None => EngineConfig::default(),
+    };
+
+    let snapshot = match args.get_one::<String>("state") {
+        Some(path) => EngineSnapshot::load(path)?.unwrap_or_default(),
+        None => EngineSnapshot::default(),
+    };
+
+    Ok(PaymentsEngine::restore(config, snapshot))
+}
+
+/// Loads the `--access-list` file and reloads it whenever the process receives SIGHUP.
+pub async fn load_access_list(
+    payments_engine: &PaymentsEngine,
+    args: &ArgMatches,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(path) = args.get_one::<String>("access_list").cloned() else {
+        return Ok(());
+    };
+    payments_engine
+        .set_access_list(ClientAccessList::from_file(&path)?)
+        .await;
+
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        let mut hangup = signal(SignalKind::hangup())?;
+        let payments_engine = payments_engine.clone();
+        tokio::spawn(async move {
+            while hangup.recv().await.is_some() {
+                match ClientAccessList::from_file(&path) {
+                    Ok(access_list) => payments_engine.set_access_list(access_list).await,
+                    Err(err) => eprintln!("{}", err),
+                }
+            }
+        });
+    }
+    Ok(())
+}
+
+pub async fn save_state(
+    payments_engine: &PaymentsEngine,
+    args: &ArgMatches,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(path) = args.get_one::<String>("state") {
+        payments_engine.snapshot().await.save(path)?;
+    }
+    Ok(())
+}
+
+/// Prints the balances, as one complete compressed chunk with `--compress-output`.
+pub fn print_state(output: Result<String, EngineError>, compression: Compression) {
+    match output {
+        Ok(output) => {
+            let mut stdout = std::io::stdout().lock();
+            if let Err(err) = compression
+                .compress(output.as_bytes())
+                .and_then(|bytes| stdout.write_all(&bytes))
+                .and_then(|_| stdout.flush())
+            {
+                eprintln!("Error writing output: {}", err);
+            }
+        }
+        Err(err) => {
+            eprintln!("Engine error : {}", err);
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use clap::{Arg, ArgAction, ArgMatches, Command};
+
+use payments_engine::export::camt053::camt053_document;
+use payments_engine::export::mt940::mt940_statement;
+use payments_engine::export::statement_options::StatementOptions;
+use payments_engine::history::account_history::{Balances, PeriodActivity};
+use payments_engine::snapshot::engine_snapshot::EngineSnapshot;
+
+use crate::commands::args::{from_arg, period_from_args, state_arg, to_arg};
+
+pub fn command() -> Command {
+    Command::new("export")
+        .about("Write per-client account statements from the history in a saved state")
+        .arg(
+            Arg::new("format")
+                .help("Statement format")
+                .action(ArgAction::Set)
+                .value_name("FORMAT")
+                .value_parser(["camt053", "mt940"])
+                .required(true),
+        )
+        .arg(state_arg().required(true))
+        .arg(
+            Arg::new("output_dir")
+                .long("output-dir")
+                .help("Directory the statements are written to, one file per client")
+                .action(ArgAction::Set)
+                .value_name("DIR")
+                .value_parser(clap::builder::NonEmptyStringValueParser::new())
+                .required(true),
+        )
+        .arg(from_arg())
+        .arg(to_arg())
+        .arg(
+            Arg::new("currency")
+                .long("currency")
+                .help("ISO 4217 currency of the amounts")
+                .action(ArgAction::Set)
+                .value_name("CODE")
+                .default_value("EUR"),
+        )
+        .arg(
+            Arg::new("client")
+                .long("client")
+                .help("Only write the statement of this client, may be repeated")
+                .action(ArgAction::Append)
+                .value_name("CLIENT")
+                .value_parser(clap::value_parser!(u16)),
+        )
+}
+
+/// Writes one statement per client, for the period given, from the history kept in
+/// the state file.
+pub fn run(args: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let format = args.get_one::<String>("format").unwrap();
+    let state = args.get_one::<String>("state").unwrap();
+    let snapshot = EngineSnapshot::load(state)?.ok_or("state file not found")?;
+    if snapshot.history.clients().is_empty() {
+        eprintln!("The state has no account history, enable [history] in the configuration");
+    }
+
+    let period = period_from_args(args)?;
+    let created = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs();
+    let options =
+        StatementOptions::new(args.get_one::<String>("currency").unwrap(), period, created)?;
+    let output_dir = std::path::Path::new(args.get_one::<String>("output_dir").unwrap());
+    std::fs::create_dir_all(output_dir)?;
+    let clients: Vec<u16> = args
+        .get_many::<u16>("client")
+        .map(|clients| clients.copied().collect())
+        .unwrap_or_default();
+
+    let mut accounts: Vec<_> = snapshot.clients.iter().collect();
+    accounts.sort_by_key(|(client_id, _)| **client_id);
+    let mut written = 0;
+    for (&client_id, account) in accounts {
+        if !clients.is_empty() && !clients.contains(&client_id) {
+            continue;
+        }
+        let activity = PeriodActivity::of(
+            snapshot.history.entries(client_id),
+            period,
+            Balances::of(account),
+        );
+        let (document, extension) = match format.as_str() {
+            "mt940" => (mt940_statement(client_id, &activity, &options), "mt940"),
+            _ => (
+                camt053_document(client_id, &activity, &options),
+                "camt053.xml",
+            ),
+        };
+        std::fs::write(
+            output_dir.join(format!("{}.{}", client_id, extension)),
+            document,
+        )?;
+        written += 1;
+    }
+    eprintln!("{} statements written to {}", written, output_dir.display());
+    Ok(())
+}
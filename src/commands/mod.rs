@@ -0,0 +1,17 @@
+//! Subcommands of the command line, one module each, and what they share: arguments,
+//! loading and saving the engine, and where refused rows, outcomes and changes go.
+
+pub mod args;
+pub mod checkpoints;
+pub mod convert;
+pub mod engine_setup;
+pub mod export;
+pub mod observers;
+pub mod outcome_log;
+pub mod refused_rows;
+pub mod retry;
+pub mod review;
+#[cfg(feature = "server")]
+pub mod serve;
+pub mod statement;
+pub mod transactions;
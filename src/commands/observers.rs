@@ -0,0 +1,144 @@
+use std::sync::Arc;
+
+use clap::ArgMatches;
+
+use payments_engine::compression::OutputFile;
+use payments_engine::engine::payments_engine::PaymentsEngine;
+use payments_engine::observe::jsonl_sink::JsonlSink;
+#[cfg(feature = "webhooks")]
+use payments_engine::webhooks::dispatcher::WebhookDispatcher;
+#[cfg(feature = "webhooks")]
+use payments_engine::webhooks::outbox::Outbox;
+#[cfg(feature = "webhooks")]
+use payments_engine::webhooks::webhook_observer::WebhookObserver;
+
+/// Webhooks of the `[webhooks]` config section, delivered in the background while
+/// the engine runs.
+#[cfg(feature = "webhooks")]
+pub struct Webhooks {
+    outbox: Arc<Outbox>,
+    dispatcher: Arc<WebhookDispatcher>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+#[cfg(feature = "webhooks")]
+impl Webhooks {
+    pub async fn start(
+        payments_engine: &PaymentsEngine,
+    ) -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        let config = &payments_engine.config().webhooks;
+        if !config.is_enabled() {
+            return Ok(None);
+        }
+        let outbox = Arc::new(Outbox::open(&config.outbox)?);
+        if !outbox.is_empty() {
+            eprintln!(
+                "Webhooks: {} deliveries left by a previous run",
+                outbox.len()
+            );
+        }
+        payments_engine
+            .subscribe(Arc::new(WebhookObserver::new(
+                config.endpoints.clone(),
+                outbox.clone(),
+            )))
+            .await;
+        let dispatcher = Arc::new(WebhookDispatcher::new(config.clone(), outbox.clone())?);
+        let task = tokio::spawn({
+            let dispatcher = dispatcher.clone();
+            async move { dispatcher.run().await }
+        });
+        Ok(Some(Self {
+            outbox,
+            dispatcher,
+            task,
+        }))
+    }
+
+    pub fn outbox(&self) -> Arc<Outbox> {
+        self.outbox.clone()
+    }
+
+    /// Stops the background deliveries after a last attempt of those due. Deliveries
+    /// still waiting for a retry stay in the outbox for the next run.
+    pub async fn finish(self) {
+        self.task.abort();
+        let _ = self.task.await;
+        let left = self.dispatcher.deliver_due().await;
+        if left > 0 {
+            eprintln!("Webhooks: {} deliveries left in the outbox", left);
+        }
+    }
+}
+
+/// Built without the `webhooks` feature, a config with endpoints is refused rather than
+/// its webhooks silently dropped.
+#[cfg(not(feature = "webhooks"))]
+pub enum Webhooks {}
+
+#[cfg(not(feature = "webhooks"))]
+impl Webhooks {
+    pub async fn start(
+        payments_engine: &PaymentsEngine,
+    ) -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        match payments_engine.config().webhooks.is_enabled() {
+            true => Err("[webhooks] is configured but this build has no webhooks feature".into()),
+            false => Ok(None),
+        }
+    }
+
+    pub async fn finish(self) {
+        match self {}
+    }
+}
+
+/// Observers every command applying rows subscribes: the `--changes` feed and the
+/// webhooks of the config.
+pub struct Observers {
+    changes: Option<Arc<JsonlSink<OutputFile>>>,
+    webhooks: Option<Webhooks>,
+}
+
+impl Observers {
+    /// A resumed run appends to the `--changes` file.
+    pub async fn subscribe(
+        payments_engine: &PaymentsEngine,
+        args: &ArgMatches,
+        append: bool,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let changes = match args.get_one::<String>("changes") {
+            Some(path) => {
+                let sink = Arc::new(match append {
+                    true => JsonlSink::append_file(path)?,
+                    false => JsonlSink::from_file(path)?,
+                });
+                payments_engine.subscribe(sink.clone()).await;
+                Some(sink)
+            }
+            None => None,
+        };
+        let webhooks = Webhooks::start(payments_engine).await?;
+        Ok(Self { changes, webhooks })
+    }
+
+    /// The `--changes` feed, if any.
+    pub fn changes(&self) -> Option<Arc<JsonlSink<OutputFile>>> {
+        self.changes.clone()
+    }
+
+    /// The outbox the webhooks are queued in, if any.
+    #[cfg(feature = "webhooks")]
+    pub fn outbox(&self) -> Option<Arc<Outbox>> {
+        self.webhooks.as_ref().map(|webhooks| webhooks.outbox())
+    }
+
+    pub async fn finish(self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(changes) = &self.changes {
+            changes.flush()?;
+        }
+        if let Some(webhooks) = self.webhooks {
+            webhooks.finish().await;
+        }
+        Ok(())
+    }
+}
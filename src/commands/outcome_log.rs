@@ -0,0 +1,64 @@
+#[cfg(feature = "parquet")]
+use std::sync::{Arc, Mutex};
+
+use clap::ArgMatches;
+
+#[cfg(feature = "parquet")]
+use payments_engine::columnar::parquet_writer::OutcomeWriter;
+use payments_engine::engine::error::EngineError;
+use payments_engine::transaction::Transaction;
+
+/// The `--outcomes` log: every row read and what the engine made of it, as Parquet.
+#[derive(Clone, Default)]
+pub struct OutcomeLog {
+    #[cfg(feature = "parquet")]
+    writer: Option<Arc<Mutex<OutcomeWriter<std::fs::File>>>>,
+}
+
+#[cfg(feature = "parquet")]
+impl OutcomeLog {
+    pub fn from_args(args: &ArgMatches) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self {
+            writer: match args.get_one::<String>("outcomes") {
+                Some(path) => Some(Arc::new(Mutex::new(OutcomeWriter::from_file(path)?))),
+                None => None,
+            },
+        })
+    }
+
+    pub fn record(&self, transaction: &Transaction, result: &Result<(), EngineError>) {
+        let Some(writer) = &self.writer else {
+            return;
+        };
+        let outcome = match result {
+            Ok(_) => "applied".to_string(),
+            Err(err) => err.to_string(),
+        };
+        if let Err(err) = writer.lock().unwrap().write(transaction, &outcome) {
+            eprintln!("{}", err);
+        }
+    }
+
+    /// Completes the file, once every input is done.
+    pub fn close(self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(writer) = self.writer {
+            let writer = Arc::try_unwrap(writer).map_err(|_| "outcome log still in use")?;
+            writer.into_inner().unwrap().close()?;
+        }
+        Ok(())
+    }
+}
+
+/// Built without Parquet support, there is no `--outcomes` log to write.
+#[cfg(not(feature = "parquet"))]
+impl OutcomeLog {
+    pub fn from_args(_args: &ArgMatches) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self {})
+    }
+
+    pub fn record(&self, _transaction: &Transaction, _result: &Result<(), EngineError>) {}
+
+    pub fn close(self) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+}
@@ -0,0 +1,73 @@
+use std::sync::{Arc, Mutex};
+
+use clap::ArgMatches;
+
+use payments_engine::compression::OutputFile;
+use payments_engine::engine::error::EngineError;
+use payments_engine::engine::events::RowRejected;
+use payments_engine::rejects::dead_letter_writer::DeadLetterWriter;
+use payments_engine::rejects::rejects_writer::RejectsWriter;
+use payments_engine::transaction::Transaction;
+
+/// Where refused rows go: the `--rejects` report and, for transient errors, the
+/// `--dead-letter` file. Shared by the ingestion tasks.
+#[derive(Clone, Default)]
+pub struct RefusedRows {
+    rejects: Option<Arc<Mutex<RejectsWriter<OutputFile>>>>,
+    dead_letter: Option<Arc<Mutex<DeadLetterWriter<OutputFile>>>>,
+}
+
+impl RefusedRows {
+    /// Opens the files given on the command line. A resumed run appends to them.
+    pub fn from_args(args: &ArgMatches, append: bool) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut refused_rows = Self::default();
+        if let Some(path) = args.get_one::<String>("rejects") {
+            let rejects = match append {
+                true => RejectsWriter::append_file(path)?,
+                false => RejectsWriter::from_file(path)?,
+            };
+            refused_rows.rejects = Some(Arc::new(Mutex::new(rejects)));
+        }
+        // `serve` has none: the rows it refuses are answered to their sender
+        if let Some(path) = args.try_get_one::<String>("dead_letter").ok().flatten() {
+            let dead_letter = match append {
+                true => DeadLetterWriter::append_file(path)?,
+                false => DeadLetterWriter::from_file(path)?,
+            };
+            refused_rows.dead_letter = Some(Arc::new(Mutex::new(dead_letter)));
+        }
+        Ok(refused_rows)
+    }
+
+    /// Records `transaction` refused by the engine with `err`.
+    pub fn refuse(&self, transaction: Transaction, err: &EngineError) {
+        if err.is_transient()
+            && let Some(dead_letter) = &self.dead_letter
+            && let Err(err) = dead_letter.lock().unwrap().write(&transaction)
+        {
+            eprintln!("{}", err);
+        }
+        self.reject(RowRejected {
+            transaction,
+            reason: err.to_string(),
+        });
+    }
+
+    pub fn reject(&self, rejected: RowRejected) {
+        if let Some(rejects) = &self.rejects
+            && let Err(err) = rejects.lock().unwrap().write(&rejected)
+        {
+            eprintln!("{}", err);
+        }
+    }
+
+    pub fn flush(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(rejects) = &self.rejects {
+            rejects.lock().unwrap().flush()?;
+        }
+        if let Some(dead_letter) = &self.dead_letter {
+            dead_letter.lock().unwrap().flush()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,86 @@
+use clap::{Arg, ArgAction, ArgMatches, Command};
+
+use payments_engine::engine::error::EngineError;
+use payments_engine::ingest::csv_reader::transactions_reader;
+use payments_engine::transaction::Transaction;
+
+use crate::commands::args::{
+    access_list_arg, changes_arg, config_arg, dead_letter_arg, rejects_arg, state_arg,
+};
+use crate::commands::engine_setup::{load_access_list, load_engine, save_state};
+use crate::commands::observers::Observers;
+use crate::commands::refused_rows::RefusedRows;
+
+pub fn command() -> Command {
+    Command::new("retry")
+        .about("Feed the rows of a dead-letter file back against a saved state")
+        .arg(
+            Arg::new("file")
+                .help("Dead-letter file written by --dead-letter")
+                .action(ArgAction::Set)
+                .value_name("DEAD_LETTER.csv")
+                .value_parser(clap::builder::NonEmptyStringValueParser::new())
+                .required(true),
+        )
+        .arg(config_arg())
+        .arg(state_arg().required(true))
+        .arg(access_list_arg())
+        .arg(rejects_arg())
+        .arg(dead_letter_arg())
+        .arg(changes_arg())
+}
+
+/// Feeds the rows of a dead-letter file back through the restored engine and reports,
+/// for each row, whether it now succeeds or why it is still refused.
+pub async fn run(args: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let filename = args.get_one::<String>("file").unwrap();
+
+    let payments_engine = load_engine(args)?;
+    load_access_list(&payments_engine, args).await?;
+    let observers = Observers::subscribe(&payments_engine, args, false).await?;
+    let refused_rows = RefusedRows::from_args(args, false)?;
+
+    let mut reader = transactions_reader(std::fs::File::open(filename)?);
+    let mut report = csv::Writer::from_writer(std::io::stdout());
+    report.write_record(["type", "client", "tx", "result"])?;
+    let (mut applied, mut total) = (0, 0);
+    for transaction_result in reader.deserialize() {
+        let transaction: Transaction = match transaction_result {
+            Ok(transaction) => transaction,
+            Err(err) => {
+                eprintln!("Error deserializing transaction: {}", err);
+                continue;
+            }
+        };
+        total += 1;
+        let result = match payments_engine.handle_transaction(transaction).await {
+            Ok(_) => {
+                applied += 1;
+                "applied".to_string()
+            }
+            Err(err @ (EngineError::HeldForReview(_) | EngineError::AwaitingTransaction(_))) => {
+                err.to_string()
+            }
+            Err(err) => {
+                refused_rows.refuse(transaction, &err);
+                err.to_string()
+            }
+        };
+        report.serialize((
+            transaction.t_type,
+            transaction.t_client_id,
+            transaction.transaction_id,
+            result,
+        ))?;
+    }
+    report.flush()?;
+    eprintln!("{} of {} rows now succeed", applied, total);
+
+    for rejected in payments_engine.take_rejected().await {
+        refused_rows.reject(rejected);
+    }
+    refused_rows.flush()?;
+
+    observers.finish().await?;
+    save_state(&payments_engine, args).await
+}
@@ -0,0 +1,62 @@
+use clap::{ArgMatches, Command};
+
+use crate::commands::args::{
+    access_list_arg, changes_arg, config_arg, state_arg, transaction_id_arg,
+};
+use crate::commands::engine_setup::{load_access_list, load_engine, save_state};
+use crate::commands::observers::Observers;
+
+pub fn command() -> Command {
+    Command::new("review")
+        .about("List, approve or reject transactions held for manual review")
+        .arg(config_arg())
+        .arg(state_arg().required(true))
+        .arg(access_list_arg())
+        .arg(changes_arg())
+        .subcommand(Command::new("list").about("Print the held transactions"))
+        .subcommand(
+            Command::new("approve")
+                .about("Apply a held transaction")
+                .arg(transaction_id_arg()),
+        )
+        .subcommand(
+            Command::new("reject")
+                .about("Drop a held transaction")
+                .arg(transaction_id_arg()),
+        )
+}
+
+pub async fn run(args: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let payments_engine = load_engine(args)?;
+    // An approved row is applied like any other: blocked clients stay blocked and its
+    // changes are published
+    load_access_list(&payments_engine, args).await?;
+    let observers = Observers::subscribe(&payments_engine, args, false).await?;
+
+    match args.subcommand() {
+        Some(("approve", sub_args)) => {
+            let transaction_id = *sub_args.get_one::<u32>("tx").unwrap();
+            match payments_engine.approve_review(transaction_id).await {
+                Ok(_) => println!("Transaction {} approved", transaction_id),
+                Err(err) => eprintln!("Engine error : {}", err),
+            }
+        }
+        Some(("reject", sub_args)) => {
+            let transaction_id = *sub_args.get_one::<u32>("tx").unwrap();
+            match payments_engine.reject_review(transaction_id).await {
+                Ok(_) => println!("Transaction {} rejected", transaction_id),
+                Err(err) => eprintln!("Engine error : {}", err),
+            }
+        }
+        _ => {
+            let mut writer = csv::Writer::from_writer(std::io::stdout());
+            for transaction in payments_engine.pending_reviews().await {
+                writer.serialize(transaction)?;
+            }
+            writer.flush()?;
+        }
+    }
+
+    observers.finish().await?;
+    save_state(&payments_engine, args).await
+}
@@ -0,0 +1,177 @@
+use std::sync::Arc;
+
+use clap::{Arg, ArgAction, ArgMatches, Command};
+
+use payments_engine::engine::payments_engine::PaymentsEngine;
+#[cfg(feature = "grpc")]
+use payments_engine::server::grpc_server;
+use payments_engine::server::http_server;
+
+use crate::commands::args::{access_list_arg, config_arg, rejects_arg, state_arg};
+use crate::commands::engine_setup::{load_access_list, load_engine, save_state};
+use crate::commands::observers::Webhooks;
+use crate::commands::refused_rows::RefusedRows;
+
+pub fn command() -> Command {
+    let serve = Command::new("serve")
+        .about("Serve the engine over HTTP, with live balance updates over a WebSocket")
+        .arg(
+            Arg::new("listen")
+                .long("listen")
+                .help("Address to listen on")
+                .action(ArgAction::Set)
+                .value_name("ADDRESS:PORT")
+                .default_value("127.0.0.1:8080"),
+        )
+        .arg(config_arg())
+        .arg(state_arg())
+        .arg(access_list_arg())
+        .arg(rejects_arg())
+        .arg(
+            Arg::new("save_every")
+                .long("save-every")
+                .help("Save the state, report expired rows and resolve expired disputes every this many seconds")
+                .action(ArgAction::Set)
+                .value_name("SECONDS")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .default_value("60"),
+        );
+    #[cfg(feature = "grpc")]
+    let serve = serve.arg(
+        Arg::new("grpc_listen")
+            .long("grpc-listen")
+            .help("Also serve the gRPC interface on this address")
+            .action(ArgAction::Set)
+            .value_name("ADDRESS:PORT"),
+    );
+    serve
+}
+
+/// Refuses to accept rows from beyond this host without the config's `api_token`.
+fn check_exposure(
+    payments_engine: &PaymentsEngine,
+    address: std::net::SocketAddr,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if address.ip().is_loopback() || payments_engine.config().server.api_token.is_some() {
+        return Ok(());
+    }
+    Err(format!(
+        "refusing to listen on {} without [server] api_token in the config",
+        address
+    )
+    .into())
+}
+
+/// Work the engine leaves to its caller, done every `--save-every` seconds while serving
+/// and once more on shutdown: rows expired from the pending buffer go to `--rejects`,
+/// expired disputes are resolved, queued webhooks are stored and the state is saved.
+async fn maintain(
+    payments_engine: &PaymentsEngine,
+    args: &ArgMatches,
+    refused_rows: &RefusedRows,
+    webhooks: Option<&Webhooks>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for rejected in payments_engine.take_rejected().await {
+        refused_rows.reject(rejected);
+    }
+    refused_rows.flush()?;
+    for event in payments_engine.sweep_expired_disputes().await {
+        eprintln!("{}", event);
+    }
+    #[cfg(feature = "webhooks")]
+    if let Some(webhooks) = webhooks {
+        webhooks.outbox().store_queued()?;
+    }
+    #[cfg(not(feature = "webhooks"))]
+    let _ = webhooks;
+    save_state(payments_engine, args).await
+}
+
+/// Runs the HTTP server mode, and the gRPC one if asked, until interrupted, then saves
+/// the state. The state is also saved regularly while serving, see `maintain`.
+pub async fn run(args: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let payments_engine = load_engine(args)?;
+    load_access_list(&payments_engine, args).await?;
+    let refused_rows = RefusedRows::from_args(args, true)?;
+    let webhooks = Webhooks::start(&payments_engine).await?.map(Arc::new);
+
+    let listener = tokio::net::TcpListener::bind(args.get_one::<String>("listen").unwrap()).await?;
+    check_exposure(&payments_engine, listener.local_addr()?)?;
+    eprintln!("Listening on {}", listener.local_addr()?);
+    #[cfg(feature = "grpc")]
+    let grpc_listener = match args.get_one::<String>("grpc_listen") {
+        Some(address) => {
+            let listener = tokio::net::TcpListener::bind(address).await?;
+            check_exposure(&payments_engine, listener.local_addr()?)?;
+            eprintln!("gRPC listening on {}", listener.local_addr()?);
+            Some(listener)
+        }
+        None => None,
+    };
+
+    // Both servers stop on the same Ctrl-C
+    let (stop, stopped) = tokio::sync::watch::channel(());
+    let shutdown = move || {
+        let mut stopped = stopped.clone();
+        async move {
+            let _ = stopped.changed().await;
+        }
+    };
+    tokio::spawn(async move {
+        let _ = tokio::signal::ctrl_c().await;
+        let _ = stop.send(());
+    });
+
+    let maintenance = tokio::spawn({
+        let payments_engine = payments_engine.clone();
+        let args = args.clone();
+        let refused_rows = refused_rows.clone();
+        let webhooks = webhooks.clone();
+        let period = std::time::Duration::from_secs(*args.get_one::<u64>("save_every").unwrap());
+        async move {
+            let mut interval = tokio::time::interval(period);
+            // The first tick completes immediately
+            interval.reset();
+            loop {
+                interval.tick().await;
+                if let Err(err) =
+                    maintain(&payments_engine, &args, &refused_rows, webhooks.as_deref()).await
+                {
+                    eprintln!("Error saving state: {}", err);
+                }
+            }
+        }
+    });
+
+    let router = http_server::router(payments_engine.clone()).await;
+    let http = async {
+        http_server::serve(listener, router, shutdown())
+            .await
+            .map_err(Box::<dyn std::error::Error>::from)
+    };
+    #[cfg(feature = "grpc")]
+    let grpc = async {
+        match grpc_listener {
+            Some(listener) => {
+                let service = grpc_server::service(payments_engine.clone());
+                grpc_server::serve(listener, service, shutdown())
+                    .await
+                    .map_err(Box::<dyn std::error::Error>::from)
+            }
+            None => Ok(()),
+        }
+    };
+    #[cfg(not(feature = "grpc"))]
+    let grpc = std::future::ready(Ok::<_, Box<dyn std::error::Error>>(()));
+    let served = tokio::try_join!(http, grpc);
+
+    // Cancelled at an await, so never halfway through writing the state
+    maintenance.abort();
+    let _ = maintenance.await;
+    served?;
+    maintain(&payments_engine, args, &refused_rows, webhooks.as_deref()).await?;
+    if let Some(webhooks) = webhooks.and_then(Arc::into_inner) {
+        webhooks.finish().await;
+    }
+    Ok(())
+}
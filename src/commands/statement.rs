@@ -0,0 +1,114 @@
+use std::io::Write;
+
+use clap::{Arg, ArgAction, ArgMatches, Command};
+
+use payments_engine::config::engine_config::EngineConfig;
+use payments_engine::engine::payments_engine::PaymentsEngine;
+use payments_engine::export::client_statement::{ClientStatement, StatementFormat};
+use payments_engine::history::account_history::{Balances, PeriodActivity};
+use payments_engine::input::STDIN;
+use payments_engine::snapshot::engine_snapshot::EngineSnapshot;
+
+use crate::commands::args::{config_arg, from_arg, period_from_args, state_arg, to_arg};
+use crate::commands::outcome_log::OutcomeLog;
+use crate::commands::refused_rows::RefusedRows;
+use crate::commands::transactions::start_transactions_service;
+
+pub fn command() -> Command {
+    Command::new("statement")
+        .about("Render a client's account statement as HTML or Markdown")
+        .arg(
+            Arg::new("client")
+                .help("Client ID")
+                .action(ArgAction::Set)
+                .value_name("CLIENT")
+                .value_parser(clap::value_parser!(u16))
+                .required(true),
+        )
+        .arg(config_arg())
+        .arg(state_arg().help("Load the engine state and its history from this file"))
+        .arg(
+            Arg::new("log")
+                .long("log")
+                .help("Replay these transactions, e.g. rows processed since the state was saved")
+                .action(ArgAction::Set)
+                .value_name("TRANSACTIONS_FILE")
+                .value_parser(clap::builder::NonEmptyStringValueParser::new()),
+        )
+        .group(
+            clap::ArgGroup::new("source")
+                .args(["state", "log"])
+                .multiple(true)
+                .required(true),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .help("Statement layout")
+                .action(ArgAction::Set)
+                .value_name("FORMAT")
+                .value_parser(["markdown", "html"])
+                .default_value("markdown"),
+        )
+        .arg(from_arg())
+        .arg(to_arg())
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .help("File to write, by default the standard output")
+                .action(ArgAction::Set)
+                .value_name("FILE")
+                .value_parser(clap::builder::NonEmptyStringValueParser::new()),
+        )
+}
+
+/// Renders one client's statement for support staff. The account history comes from
+/// the `--state` snapshot and from replaying the `--log` transactions on top of it, in
+/// memory: neither file is changed.
+pub async fn run(args: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let client_id = *args.get_one::<u16>("client").unwrap();
+    let format = StatementFormat::from_name(args.get_one::<String>("format").unwrap()).unwrap();
+    let period = period_from_args(args)?;
+
+    let mut config = match args.get_one::<String>("config") {
+        Some(path) => EngineConfig::from_file(path)?,
+        None => EngineConfig::default(),
+    };
+    config.history.enabled = true;
+    let snapshot = match args.get_one::<String>("state") {
+        Some(path) => EngineSnapshot::load(path)?.ok_or("state file not found")?,
+        None => EngineSnapshot::default(),
+    };
+    let payments_engine = PaymentsEngine::restore(config, snapshot);
+    if let Some(log) = args.get_one::<String>("log") {
+        start_transactions_service(
+            payments_engine.clone(),
+            log.clone(),
+            RefusedRows::default(),
+            OutcomeLog::default(),
+            None,
+        )
+        .await
+        .map_err(|_| "log was not fully read")?;
+    }
+
+    let account = payments_engine
+        .account(client_id)
+        .await
+        .ok_or_else(|| format!("client {} has no account", client_id))?;
+    let history = payments_engine.history().await;
+    if history.entries(client_id).is_empty() {
+        eprintln!(
+            "No history for client {}, the state was saved without [history] enabled",
+            client_id
+        );
+    }
+    let activity = PeriodActivity::of(history.entries(client_id), period, Balances::of(&account));
+    let statement = ClientStatement::new(client_id, period, activity).render(format);
+
+    match args.get_one::<String>("output") {
+        Some(path) if path != STDIN => std::fs::write(path, statement)?,
+        _ => std::io::stdout().write_all(statement.as_bytes())?,
+    }
+    Ok(())
+}
@@ -0,0 +1,312 @@
+//! The default command: applies the rows of a transactions file and prints the
+//! balances.
+
+use clap::{Arg, ArgAction, ArgMatches};
+use tokio::task::JoinSet;
+
+#[cfg(feature = "parquet")]
+use payments_engine::columnar::parquet_reader::is_parquet;
+#[cfg(feature = "parquet")]
+use payments_engine::columnar::parquet_writer::write_balances;
+use payments_engine::engine::error::EngineError;
+use payments_engine::engine::payments_engine::PaymentsEngine;
+use payments_engine::ingest::csv_reader::spawn_reader;
+#[cfg(feature = "parquet")]
+use payments_engine::ingest::parquet_reader::spawn_parquet_reader;
+use payments_engine::input::Input;
+use payments_engine::snapshot::input_checkpoint::InputCheckpoint;
+
+use crate::commands::args::*;
+use crate::commands::checkpoints::Checkpoints;
+use crate::commands::engine_setup::{load_access_list, load_engine, print_state, save_state};
+use crate::commands::observers::Observers;
+use crate::commands::outcome_log::OutcomeLog;
+use crate::commands::refused_rows::RefusedRows;
+
+/// Arguments of the default command.
+pub fn args() -> Vec<Arg> {
+    let mut args = vec![
+        Arg::new("file")
+            .display_order(1)
+            .alias("metadata")
+            .help("Provide transtactions.csv file, a binary or Parquet file, or - to read the standard input")
+            .action(ArgAction::Set)
+            .value_name("TRANSACTIONS_FILE.csv")
+            .value_parser(clap::builder::NonEmptyStringValueParser::new())
+            .required(true),
+        config_arg(),
+        state_arg(),
+        access_list_arg(),
+        rejects_arg(),
+        dead_letter_arg(),
+        checkpoint_every_arg(),
+        resume_arg(),
+        emit_every_arg(),
+        compress_output_arg(),
+    ];
+    #[cfg(feature = "parquet")]
+    args.extend([parquet_output_arg(), outcomes_arg()]);
+    args.push(changes_arg());
+    args
+}
+
+/// Rows read ahead of the engine, per input.
+const READ_AHEAD_ROWS: usize = 1024;
+
+/// Rows between two sweeps of expired disputes. Each sweep scans every stored
+/// transaction, so it is not run after every row.
+const SWEEP_EVERY_ROWS: u64 = 10_000;
+
+/// Built without Parquet support, Parquet inputs are still recognised by their extension
+/// so that they are refused instead of read as CSV.
+#[cfg(not(feature = "parquet"))]
+fn is_parquet(path: &str) -> bool {
+    path.ends_with(".parquet")
+}
+
+#[cfg(not(feature = "parquet"))]
+fn spawn_parquet_reader(
+    _path: &str,
+    _capacity: usize,
+) -> std::io::Result<tokio::sync::mpsc::Receiver<payments_engine::ingest::csv_reader::ReadRow>> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "built without the parquet feature",
+    ))
+}
+
+pub async fn start_transactions_service(
+    payments_engine: PaymentsEngine,
+    filename: String,
+    refused_rows: RefusedRows,
+    outcome_log: OutcomeLog,
+    checkpoints: Option<Checkpoints>,
+) -> Result<(), ()> {
+    let path = filename.trim();
+
+    let parquet = is_parquet(path);
+    // Pipes and the standard input can neither be fingerprinted nor resumed, and Parquet
+    // files are read a batch at a time
+    let mut checkpoints = match checkpoints {
+        Some(checkpoints) if parquet || !Input::is_regular_file(path) => {
+            if checkpoints.resume {
+                eprintln!(
+                    "{} is not a regular CSV or binary file and can not be resumed",
+                    path
+                );
+            }
+            None
+        }
+        checkpoints => checkpoints,
+    };
+
+    let mut resume_from = None;
+    if let Some(checkpoints) = &checkpoints
+        && checkpoints.resume
+        && let Some(checkpoint) = payments_engine.input_checkpoint().await
+    {
+        match checkpoint.is_for(path) {
+            Ok(true) => {
+                eprintln!("Resuming {} at record {}", path, checkpoint.record);
+                resume_from = Some(checkpoint.position());
+            }
+            Ok(false) => eprintln!("Checkpoint is for another input, starting from the beginning"),
+            Err(err) => eprintln!("{}", err),
+        }
+    }
+
+    let mut rows = if parquet {
+        spawn_parquet_reader(path, READ_AHEAD_ROWS)
+    } else {
+        let input =
+            Input::open(path).map_err(|err| eprintln!("Error opening {}: {}", path, err))?;
+        spawn_reader(input, resume_from, READ_AHEAD_ROWS)
+    }
+    .map_err(|err| eprintln!("Error reading {}: {}", path, err))?;
+
+    let mut last_position = None;
+    while let Some(row) = rows.recv().await {
+        match row.transaction {
+            Ok(transaction) => {
+                let result = payments_engine.handle_transaction(transaction).await;
+                outcome_log.record(&transaction, &result);
+                match result {
+                    Ok(_) => {}
+                    // Parked rows are not refused (yet)
+                    Err(EngineError::HeldForReview(_) | EngineError::AwaitingTransaction(_)) => {}
+                    Err(err) => refused_rows.refuse(transaction, &err),
+                }
+            }
+            Err(err) if err.is_fatal() => {
+                eprintln!("Error reading {}: {}", path, err);
+                return Err(());
+            }
+            Err(err) => eprintln!("Error deserializing transaction: {}", err),
+        }
+
+        // Before a checkpoint, so the state it saves has the disputes already resolved
+        if row.position.record().is_multiple_of(SWEEP_EVERY_ROWS) {
+            for event in payments_engine.sweep_expired_disputes().await {
+                eprintln!("{}", event);
+            }
+        }
+        if let Some(checkpoints) = &mut checkpoints
+            && checkpoints.is_due(&row.position)
+            && let Err(err) = checkpoints
+                .save(&payments_engine, path, &row.position, &refused_rows)
+                .await
+        {
+            eprintln!("Error saving checkpoint: {}", err);
+        }
+        last_position = Some(row.position);
+    }
+
+    // The final state is saved once every input is done
+    if checkpoints.is_some()
+        && let Some(position) = last_position
+    {
+        match InputCheckpoint::take(path, &position) {
+            Ok(checkpoint) => payments_engine.set_input_checkpoint(checkpoint).await,
+            Err(err) => eprintln!("{}", err),
+        }
+    }
+    Ok(())
+}
+
+/// Prints the balances every `--emit-every` seconds and, on Unix, whenever the process
+/// receives SIGUSR1, until the returned task is aborted.
+fn emit_balances(
+    payments_engine: &PaymentsEngine,
+    args: &ArgMatches,
+) -> Result<tokio::task::JoinHandle<()>, Box<dyn std::error::Error>> {
+    let mut interval = args
+        .get_one::<u64>("emit_every")
+        .map(|seconds| tokio::time::interval(std::time::Duration::from_secs(*seconds)));
+    if let Some(interval) = &mut interval {
+        // The first tick completes immediately
+        interval.reset();
+    }
+    #[cfg(unix)]
+    let mut user_signal =
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::user_defined1())?;
+
+    let compression = output_compression(args);
+    let payments_engine = payments_engine.clone();
+    Ok(tokio::spawn(async move {
+        loop {
+            let tick = async {
+                match &mut interval {
+                    Some(interval) => {
+                        interval.tick().await;
+                    }
+                    None => std::future::pending().await,
+                }
+            };
+            #[cfg(unix)]
+            tokio::select! {
+                _ = tick => {}
+                _ = user_signal.recv() => {}
+            }
+            #[cfg(not(unix))]
+            tick.await;
+
+            print_state(payments_engine.write_state().await, compression);
+        }
+    }))
+}
+
+pub async fn run(args: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let filename = args.get_one::<String>("file").unwrap().clone();
+
+    let payments_engine = load_engine(args)?;
+    load_access_list(&payments_engine, args).await?;
+    // Decisions restored from a previous run were reported by that run
+    let reported_decisions = payments_engine.rule_decision_count().await;
+    let checkpoints = Checkpoints::from_args(args);
+    let refused_rows = RefusedRows::from_args(args, args.get_flag("resume"))?;
+    let outcome_log = OutcomeLog::from_args(args)?;
+    let observers = Observers::subscribe(&payments_engine, args, args.get_flag("resume")).await?;
+    let checkpoints = checkpoints.map(|checkpoints| Checkpoints {
+        changes: observers.changes(),
+        #[cfg(feature = "webhooks")]
+        outbox: observers.outbox(),
+        ..checkpoints
+    });
+
+    let emitter = emit_balances(&payments_engine, args)?;
+
+    let mut set = JoinSet::new();
+    set.spawn(start_transactions_service(
+        payments_engine.clone(),
+        filename,
+        refused_rows.clone(),
+        outcome_log.clone(),
+        checkpoints,
+    ));
+    // set.spawn(start_transactions_service(
+    //     payments_engine.clone(),
+    //     "transactions_large.csv".to_string(),
+    // )); // //Just to test if it work as expected
+
+    let results = set.join_all().await;
+    emitter.abort();
+    refused_rows.flush()?;
+    outcome_log.close()?;
+    if results.iter().any(Result::is_err) {
+        return Err("input was not fully read".into());
+    }
+
+    // Without a state file, rows still waiting for their deposit would be lost
+    if args.get_one::<String>("state").is_none() {
+        payments_engine.flush_pending().await;
+    }
+    for rejected in payments_engine.take_rejected().await {
+        refused_rows.reject(rejected);
+    }
+    refused_rows.flush()?;
+
+    for event in payments_engine.sweep_expired_disputes().await {
+        eprintln!("{}", event);
+    }
+
+    let decisions = payments_engine
+        .rule_decisions_since(reported_decisions)
+        .await;
+    let not_logged =
+        payments_engine.rule_decision_count().await - reported_decisions - decisions.len() as u64;
+    if not_logged > 0 {
+        eprintln!(
+            "{} earlier risk rule decisions dropped from the log",
+            not_logged
+        );
+    }
+    for decision in decisions {
+        eprintln!("{}", decision);
+    }
+
+    for account in payments_engine.accounts_over_limit().await {
+        eprintln!("{}", account);
+    }
+
+    if payments_engine.has_fee_schedule() {
+        eprintln!("Fee revenue: {:.4}", payments_engine.fee_revenue().await);
+    }
+
+    observers.finish().await?;
+    save_state(&payments_engine, args).await?;
+
+    #[cfg(feature = "parquet")]
+    if let Some(path) = args.get_one::<String>("parquet_output") {
+        write_balances(
+            std::fs::File::create(path)?,
+            &payments_engine.accounts().await,
+        )?;
+    }
+
+    print_state(
+        payments_engine.write_state().await,
+        output_compression(args),
+    );
+
+    Ok(())
+}
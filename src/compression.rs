@@ -0,0 +1,181 @@
+use std::io::{BufRead, Read, Write};
+use std::path::Path;
+
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// A writer that may compress, e.g. a rejects report named `rejects.csv.gz`.
+pub type OutputFile = Box<dyn Write + Send>;
+
+/// Compression of an input or output stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// Compression named on the command line: `none`, `gzip` or `zstd`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "none" => Some(Compression::None),
+            "gzip" => Some(Compression::Gzip),
+            "zstd" => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Compression implied by a `.gz` or `.zst` file extension.
+    pub fn from_extension(path: impl AsRef<Path>) -> Self {
+        match path
+            .as_ref()
+            .extension()
+            .and_then(|extension| extension.to_str())
+        {
+            Some("gz" | "gzip") => Compression::Gzip,
+            Some("zst" | "zstd") => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+
+    /// Compression recognised from the first bytes of a stream.
+    pub fn from_magic(head: &[u8]) -> Self {
+        if head.starts_with(GZIP_MAGIC) {
+            Compression::Gzip
+        } else if head.starts_with(ZSTD_MAGIC) {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+
+    /// Detects the compression of `reader` from its magic bytes, without consuming them.
+    pub fn detect(reader: &mut impl BufRead) -> std::io::Result<Self> {
+        Ok(Self::from_magic(reader.fill_buf()?))
+    }
+
+    /// Wraps `reader` so it yields the decompressed stream. Concatenated gzip members
+    /// and zstd frames are read one after the other.
+    pub fn decoder<R>(self, reader: R) -> std::io::Result<Box<dyn Read + Send>>
+    where
+        R: BufRead + Send + 'static,
+    {
+        Ok(match self {
+            Compression::None => Box::new(reader),
+            Compression::Gzip => Box::new(MultiGzDecoder::new(reader)),
+            Compression::Zstd => Box::new(zstd::Decoder::with_buffer(reader)?),
+        })
+    }
+
+    /// Wraps `writer` so everything written to it is compressed. The stream is
+    /// completed when the returned writer is dropped.
+    pub fn encoder<W>(self, writer: W) -> std::io::Result<OutputFile>
+    where
+        W: Write + Send + 'static,
+    {
+        Ok(match self {
+            Compression::None => Box::new(writer),
+            Compression::Gzip => Box::new(GzEncoder::new(writer, flate2::Compression::default())),
+            Compression::Zstd => Box::new(zstd::Encoder::new(writer, 0)?.auto_finish()),
+        })
+    }
+
+    /// Compresses `bytes` as one complete gzip member or zstd frame. Such chunks can be
+    /// written one after the other and still decompress as a single stream.
+    pub fn compress(self, bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Compression::None => Ok(bytes.to_vec()),
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(bytes)?;
+                encoder.finish()
+            }
+            Compression::Zstd => zstd::encode_all(bytes, 0),
+        }
+    }
+}
+
+/// Creates, or with `append` continues, the file at `path`, compressed according to its
+/// extension. Returns the writer and whether the file was empty.
+pub fn create_output(path: impl AsRef<Path>, append: bool) -> std::io::Result<(OutputFile, bool)> {
+    let path = path.as_ref();
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(append)
+        .truncate(!append)
+        .open(path)?;
+    let empty = file.metadata()?.len() == 0;
+    let writer = std::io::BufWriter::new(file);
+    Ok((Compression::from_extension(path).encoder(writer)?, empty))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    const CSV: &[u8] = b"type,client,tx,amount\ndeposit,1,1,1.0\n";
+
+    fn decompress(bytes: Vec<u8>) -> Vec<u8> {
+        let mut reader = std::io::Cursor::new(bytes);
+        let compression = Compression::detect(&mut reader).unwrap();
+        let mut output = Vec::new();
+        compression
+            .decoder(reader)
+            .unwrap()
+            .read_to_end(&mut output)
+            .unwrap();
+        output
+    }
+
+    #[test]
+    fn detect_compression() {
+        assert_eq!(Compression::from_extension("in.csv.gz"), Compression::Gzip);
+        assert_eq!(Compression::from_extension("in.csv.zst"), Compression::Zstd);
+        assert_eq!(Compression::from_extension("in.csv"), Compression::None);
+
+        assert_eq!(
+            Compression::from_magic(&Compression::Gzip.compress(CSV).unwrap()),
+            Compression::Gzip
+        );
+        assert_eq!(
+            Compression::from_magic(&Compression::Zstd.compress(CSV).unwrap()),
+            Compression::Zstd
+        );
+        assert_eq!(Compression::from_magic(CSV), Compression::None);
+    }
+
+    #[test]
+    fn round_trip() {
+        for compression in [Compression::None, Compression::Gzip, Compression::Zstd] {
+            assert_eq!(decompress(compression.compress(CSV).unwrap()), CSV);
+        }
+    }
+
+    #[test]
+    fn concatenated_chunks() {
+        for compression in [Compression::Gzip, Compression::Zstd] {
+            let mut bytes = compression.compress(b"first\n").unwrap();
+            bytes.extend(compression.compress(b"second\n").unwrap());
+            assert_eq!(decompress(bytes), b"first\nsecond\n");
+        }
+    }
+
+    #[test]
+    fn compressed_output_file() {
+        let path =
+            std::env::temp_dir().join(format!("compressed_output_{}.csv.zst", std::process::id()));
+        for append in [false, true] {
+            let (mut writer, _) = create_output(&path, append).unwrap();
+            writer.write_all(b"row\n").unwrap();
+        }
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(decompress(bytes), b"row\nrow\n");
+    }
+}
@@ -0,0 +1,344 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::config::error::ConfigError;
+use crate::fees::fee_schedule::FeeSchedule;
+use crate::rules::risk_rule::RiskRule;
+use crate::types::{Amount, ClientId, EventPosition, Timestamp};
+use crate::webhooks::webhook_config::WebhooksConfig;
+
+/// Engine settings loaded from a TOML file. Every section is optional and the
+/// defaults keep the behaviour of an engine built with `PaymentsEngine::new`.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct EngineConfig {
+    pub disputes: DisputeConfig,
+    pub chargebacks: ChargebackConfig,
+    pub fees: FeeSchedule,
+    pub credit_limits: CreditLimitConfig,
+    pub rules: Vec<RiskRule>,
+    pub pending: PendingConfig,
+    pub history: HistoryConfig,
+    pub webhooks: WebhooksConfig,
+    pub server: ServerConfig,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DisputeConfig {
+    /// How long after a deposit a dispute is still accepted.
+    pub window: Option<Window>,
+    /// How long a dispute may stay open before a sweep resolves it.
+    pub auto_resolve_after: Option<Window>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChargebackConfig {
+    /// Whether a reversed chargeback also unlocks the account it locked.
+    pub reversal_unlocks_account: bool,
+}
+
+/// Buffering of dispute, resolve and chargeback rows that arrive before the deposit
+/// they refer to. Disabled unless `expire_after` is set.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PendingConfig {
+    /// How long a row waits for its deposit before it is rejected.
+    pub expire_after: Option<Window>,
+    /// Rows processed after which a row is rejected when a `seconds` window can not be
+    /// judged because the rows carry no timestamps.
+    pub untimed_expire_after: u64,
+}
+
+impl Default for PendingConfig {
+    fn default() -> Self {
+        Self {
+            expire_after: None,
+            untimed_expire_after: 100_000,
+        }
+    }
+}
+
+/// Recording of every applied row per client, which statements are built from.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistoryConfig {
+    pub enabled: bool,
+}
+
+/// How far each client's available funds may go below zero through withdrawals.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CreditLimitConfig {
+    pub default: Amount,
+    pub clients: HashMap<ClientId, Amount>,
+}
+
+impl CreditLimitConfig {
+    pub fn limit_for(&self, client_id: ClientId) -> Amount {
+        self.clients
+            .get(&client_id)
+            .copied()
+            .unwrap_or(self.default)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.default < Amount::ZERO {
+            return Err("negative default credit limit".to_string());
+        }
+        match self
+            .clients
+            .iter()
+            .find(|(_, limit)| **limit < Amount::ZERO)
+        {
+            Some((client_id, _)) => Err(format!("negative credit limit for client {}", client_id)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Access to the `serve` mode. Without a token anyone reaching the listen address may
+/// submit rows, so `serve` only listens on loopback addresses then.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Token requests must carry as `Authorization: Bearer <token>`.
+    pub api_token: Option<String>,
+}
+
+impl ServerConfig {
+    /// Whether a request with this `Authorization` header may go on. The token is
+    /// compared in constant time, so timing does not tell how much of it matched.
+    pub fn authorized(&self, authorization: Option<&[u8]>) -> bool {
+        let Some(token) = &self.api_token else {
+            return true;
+        };
+        let Some(given) = authorization.and_then(|header| header.strip_prefix(b"Bearer ")) else {
+            return false;
+        };
+        given.len() == token.len()
+            && given
+                .iter()
+                .zip(token.as_bytes())
+                .fold(0, |difference, (a, b)| difference | (a ^ b))
+                == 0
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match &self.api_token {
+            Some(token) if token.is_empty() => Err("empty server api_token".to_string()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A span measured either in seconds of row timestamps or in processed rows.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase", deny_unknown_fields)]
+pub enum Window {
+    Seconds(Timestamp),
+    Events(u64),
+}
+
+impl Window {
+    /// Whether more than the window has passed between `from` and `to`. A time window
+    /// can not be judged without both timestamps and is then considered open.
+    pub fn exceeded(&self, from: EventPosition, to: EventPosition) -> bool {
+        match *self {
+            Window::Seconds(seconds) => match (from.timestamp, to.timestamp) {
+                (Some(from), Some(to)) => to.saturating_sub(from) > seconds,
+                _ => false,
+            },
+            Window::Events(events) => to.sequence.saturating_sub(from.sequence) > events,
+        }
+    }
+
+    /// Like `exceeded`, but a time window that can not be judged falls back to a window
+    /// of `events` rows, so state kept per window still ends for rows without timestamps.
+    pub fn exceeded_or_after(&self, events: u64, from: EventPosition, to: EventPosition) -> bool {
+        match (*self, from.timestamp, to.timestamp) {
+            (Window::Seconds(_), None, _) | (Window::Seconds(_), _, None) => {
+                Window::Events(events).exceeded(from, to)
+            }
+            _ => self.exceeded(from, to),
+        }
+    }
+}
+
+impl EngineConfig {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let content = std::fs::read_to_string(path)?;
+        Self::from_toml(&content)
+    }
+
+    pub fn from_toml(content: &str) -> Result<Self, ConfigError> {
+        let config: Self = toml::from_str(content)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks the values the parser accepts but the engine can not apply.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        self.fees.validate().map_err(ConfigError::Invalid)?;
+        self.credit_limits
+            .validate()
+            .map_err(ConfigError::Invalid)?;
+        self.server.validate().map_err(ConfigError::Invalid)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_config() {
+        assert_eq!(
+            EngineConfig::from_toml("").unwrap(),
+            EngineConfig::default()
+        );
+    }
+
+    #[test]
+    fn dispute_config() {
+        let config = EngineConfig::from_toml(
+            r#"
+            [disputes]
+            window = { seconds = 86400 }
+            auto_resolve_after = { events = 100 }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.disputes.window, Some(Window::Seconds(86400)));
+        assert_eq!(
+            config.disputes.auto_resolve_after,
+            Some(Window::Events(100))
+        );
+    }
+
+    #[test]
+    fn pending_config() {
+        let config = EngineConfig::from_toml(
+            r#"
+            [pending]
+            expire_after = { events = 1000 }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.pending.expire_after, Some(Window::Events(1000)));
+    }
+
+    #[test]
+    fn chargeback_config() {
+        let config =
+            EngineConfig::from_toml("[chargebacks]\nreversal_unlocks_account = true").unwrap();
+
+        assert!(config.chargebacks.reversal_unlocks_account);
+    }
+
+    #[test]
+    fn fees_config() {
+        let config = EngineConfig::from_toml(
+            r#"
+            [fees.tiers.default]
+            withdrawal = { flat = 0.5 }
+            "#,
+        )
+        .unwrap();
+
+        assert!(config.fees.tier_for(1).unwrap().withdrawal.is_some());
+    }
+
+    #[test]
+    fn negative_fees_config() {
+        for fee in ["{ flat = -0.5 }", "{ percent = -1 }"] {
+            let content = format!("[fees.tiers.default]\ndeposit = {}", fee);
+            assert!(matches!(
+                EngineConfig::from_toml(&content),
+                Err(ConfigError::Invalid(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn credit_limits_config() {
+        let config = EngineConfig::from_toml(
+            r#"
+            [credit_limits]
+            default = 10
+
+            [credit_limits.clients]
+            7 = 250.5
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.credit_limits.limit_for(1), Amount::TEN);
+        assert_eq!(config.credit_limits.limit_for(7), Amount::new(2505, 1));
+    }
+
+    #[test]
+    fn negative_credit_limits_config() {
+        for content in [
+            "[credit_limits]\ndefault = -1",
+            "[credit_limits.clients]\n7 = -250",
+        ] {
+            assert!(matches!(
+                EngineConfig::from_toml(content),
+                Err(ConfigError::Invalid(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn server_config() {
+        let config = EngineConfig::from_toml("[server]\napi_token = \"s3cret\"").unwrap();
+        assert!(config.server.authorized(Some(b"Bearer s3cret")));
+        assert!(!config.server.authorized(Some(b"Bearer s3cre")));
+        assert!(!config.server.authorized(Some(b"Bearer s3crex")));
+        assert!(!config.server.authorized(Some(b"s3cret")));
+        assert!(!config.server.authorized(None));
+        assert!(ServerConfig::default().authorized(None));
+        assert!(matches!(
+            EngineConfig::from_toml("[server]\napi_token = \"\""),
+            Err(ConfigError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn invalid_config() {
+        assert!(EngineConfig::from_toml("[disputes]\nwindow = { days = 1 }").is_err());
+    }
+
+    #[test]
+    fn window_exceeded() {
+        let from = EventPosition {
+            sequence: 10,
+            timestamp: Some(1_000),
+        };
+        let to = EventPosition {
+            sequence: 15,
+            timestamp: Some(1_100),
+        };
+
+        assert!(Window::Seconds(99).exceeded(from, to));
+        assert!(!Window::Seconds(100).exceeded(from, to));
+        assert!(Window::Events(4).exceeded(from, to));
+        assert!(!Window::Events(5).exceeded(from, to));
+
+        let untimed = EventPosition {
+            sequence: 15,
+            timestamp: None,
+        };
+        assert!(!Window::Seconds(0).exceeded(from, untimed));
+        assert!(Window::Seconds(0).exceeded_or_after(4, from, untimed));
+        assert!(!Window::Seconds(0).exceeded_or_after(5, from, untimed));
+        assert!(!Window::Seconds(100).exceeded_or_after(0, from, to));
+        assert!(!Window::Events(5).exceeded_or_after(0, from, to));
+    }
+}
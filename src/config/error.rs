@@ -0,0 +1,13 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("Error reading config file: {0}")]
+    Read(#[from] std::io::Error),
+
+    #[error("Invalid config file: {0}")]
+    Parse(#[from] toml::de::Error),
+
+    #[error("Invalid config value: {0}")]
+    Invalid(String),
+}
@@ -0,0 +1,2 @@
+pub mod engine_config;
+pub mod error;
@@ -10,6 +10,9 @@ pub enum EngineError {
     #[error("Client not found")]
     ClientNotFound,
 
+    #[error("Client blocked: {0}")]
+    ClientBlocked(ClientId),
+
     #[error("Client account error: {0}")]
     ClientAccountError(#[from] ClientAccountError),
 
@@ -25,12 +28,77 @@ pub enum EngineError {
     #[error("Transaction not disputed: {0}")]
     TransactionNotDisputed(TransactionId),
 
+    #[error("Transaction resolved already: {0}")]
+    TransactionAlreadyResolved(TransactionId),
+
+    #[error("Transaction charged back already: {0}")]
+    TransactionAlreadyChargedBack(TransactionId),
+
+    #[error("Transaction not charged back: {0}")]
+    TransactionNotChargedBack(TransactionId),
+
+    #[error("Transaction chargeback reversed already: {0}")]
+    TransactionReversed(TransactionId),
+
+    #[error("Dispute window expired for transaction: {0}")]
+    DisputeWindowExpired(TransactionId),
+
+    #[error("Invalid dispute amount for transaction: {0}")]
+    InvalidDisputeAmount(TransactionId),
+
     #[error("Transaction with ID '{0}' is not owned by the client {1}")]
     NotClientOwnedTransaction(TransactionId, ClientId),
 
     #[error("Transaction already exists")]
     TransactionAlreadyExists,
 
+    #[error("Transaction {0} rejected by risk rule: {1}")]
+    RuleRejected(TransactionId, String),
+
+    #[error("Transaction held for review: {0}")]
+    HeldForReview(TransactionId),
+
+    #[error("Transaction not pending review: {0}")]
+    NotPendingReview(TransactionId),
+
+    #[error("Transaction waiting for its target transaction: {0}")]
+    AwaitingTransaction(TransactionId),
+
     #[error("Error writing console")]
     WriteBuffer,
 }
+
+impl EngineError {
+    /// Whether the row may succeed when retried later, e.g. once a missing deposit
+    /// arrived or the access list changed. Such rows go to the dead-letter file.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            EngineError::ClientNotFound
+                | EngineError::ClientBlocked(_)
+                | EngineError::TransactionNotFound(_)
+                | EngineError::ClientAccountError(
+                    ClientAccountError::InsufficientBalance | ClientAccountError::Locked
+                )
+        )
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn transient_errors() {
+        assert!(EngineError::ClientNotFound.is_transient());
+        assert!(EngineError::TransactionNotFound(1).is_transient());
+        assert!(
+            EngineError::ClientAccountError(ClientAccountError::InsufficientBalance).is_transient()
+        );
+        assert!(!EngineError::TransactionAlreadyExists.is_transient());
+        assert!(
+            !EngineError::ClientAccountError(ClientAccountError::NegativeAmount).is_transient()
+        );
+        assert!(!EngineError::RuleRejected(1, "amount".to_string()).is_transient());
+    }
+}
@@ -0,0 +1,96 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::history::account_history::Balances;
+use crate::transaction::Transaction;
+use crate::types::{Amount, ClientId, EventPosition, TransactionId};
+
+/// A dispute closed by `PaymentsEngine::sweep_expired_disputes` because it stayed
+/// open past the configured deadline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DisputeAutoResolved {
+    pub client_id: ClientId,
+    pub transaction_id: TransactionId,
+    pub amount: Amount,
+}
+
+impl fmt::Display for DisputeAutoResolved {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Dispute auto-resolved: client {}, transaction {}, released {:.4}",
+            self.client_id, self.transaction_id, self.amount
+        )
+    }
+}
+
+/// A row refused outside of `PaymentsEngine::handle_transaction`, e.g. a pending row
+/// that expired or failed once its target transaction arrived.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowRejected {
+    pub transaction: Transaction,
+    pub reason: String,
+}
+
+impl fmt::Display for RowRejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Row rejected: client {}, transaction {}: {}",
+            self.transaction.t_client_id, self.transaction.transaction_id, self.reason
+        )
+    }
+}
+
+/// An account whose available funds went below its credit limit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountOverLimit {
+    pub client_id: ClientId,
+    pub available: Amount,
+    pub credit_limit: Amount,
+}
+
+impl fmt::Display for AccountOverLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Account over credit limit: client {}, available {:.4}, limit {:.4}",
+            self.client_id, self.available, self.credit_limit
+        )
+    }
+}
+
+/// A client's account changed because `transaction` was applied. Published to the
+/// observers subscribed with `PaymentsEngine::subscribe`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccountChanged {
+    pub client_id: ClientId,
+    pub position: EventPosition,
+    pub transaction: Transaction,
+    pub before: Balances,
+    pub after: Balances,
+}
+
+impl AccountChanged {
+    /// Whether the change locked the account.
+    pub fn locked(&self) -> bool {
+        self.after.locked && !self.before.locked
+    }
+}
+
+impl fmt::Display for AccountChanged {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Account changed: client {}, {} {}, available {:.4}, held {:.4}, total {:.4}{}",
+            self.client_id,
+            self.transaction.t_type.name(),
+            self.transaction.transaction_id,
+            self.after.available,
+            self.after.held,
+            self.after.total,
+            if self.after.locked { ", locked" } else { "" }
+        )
+    }
+}
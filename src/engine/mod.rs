@@ -1,2 +1,4 @@
 pub mod error;
+pub mod events;
 pub mod payments_engine;
+pub mod transaction_state;
@@ -0,0 +1,92 @@
+use serde::{Deserialize, Serialize};
+
+use crate::engine::error::EngineError;
+use crate::types::TransactionId;
+
+/// Lifecycle of a stored deposit with respect to disputes. A deposit may be disputed in
+/// portions, so the state summarises them: the open dispute first, then a standing
+/// chargeback, a reversed one, and a resolved one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransactionState {
+    Settled,
+    Disputed,
+    Resolved,
+    ChargedBack,
+    Reversed,
+}
+
+/// Rows that move a stored transaction through its lifecycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisputeAction {
+    Dispute,
+    Resolve,
+    Chargeback,
+    ReverseChargeback,
+}
+
+impl TransactionState {
+    /// Why `action` is refused once nothing is left for it: no undisputed value for a
+    /// dispute, nothing disputed for a resolve or chargeback, nothing charged back for a
+    /// reversal. The error names what happened to the rest of the transaction.
+    pub fn refusal(self, action: DisputeAction, transaction_id: TransactionId) -> EngineError {
+        use DisputeAction::*;
+        use TransactionState::*;
+
+        match (self, action) {
+            (Disputed, Dispute) => EngineError::TransactionAlreadyDisputed(transaction_id),
+            (Resolved, Dispute) => EngineError::TransactionAlreadyResolved(transaction_id),
+            (ChargedBack, Dispute | Chargeback) => {
+                EngineError::TransactionAlreadyChargedBack(transaction_id)
+            }
+            (Reversed, _) => EngineError::TransactionReversed(transaction_id),
+            // Only a deposit of zero has nothing to dispute while settled
+            (Settled, Dispute) => EngineError::InvalidDisputeAmount(transaction_id),
+            (_, Resolve | Chargeback) => EngineError::TransactionNotDisputed(transaction_id),
+            (_, ReverseChargeback) => EngineError::TransactionNotChargedBack(transaction_id),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn refusals() {
+        use DisputeAction::*;
+        use TransactionState::*;
+
+        assert_eq!(
+            Disputed.refusal(Dispute, 1),
+            EngineError::TransactionAlreadyDisputed(1)
+        );
+        assert_eq!(
+            Resolved.refusal(Dispute, 1),
+            EngineError::TransactionAlreadyResolved(1)
+        );
+        assert_eq!(
+            ChargedBack.refusal(Dispute, 1),
+            EngineError::TransactionAlreadyChargedBack(1)
+        );
+        assert_eq!(
+            ChargedBack.refusal(Chargeback, 1),
+            EngineError::TransactionAlreadyChargedBack(1)
+        );
+        assert_eq!(
+            Settled.refusal(Resolve, 1),
+            EngineError::TransactionNotDisputed(1)
+        );
+        assert_eq!(
+            Resolved.refusal(Chargeback, 1),
+            EngineError::TransactionNotDisputed(1)
+        );
+        assert_eq!(
+            Settled.refusal(ReverseChargeback, 1),
+            EngineError::TransactionNotChargedBack(1)
+        );
+        assert_eq!(
+            Reversed.refusal(Dispute, 1),
+            EngineError::TransactionReversed(1)
+        );
+    }
+}
@@ -0,0 +1,114 @@
+//! Conversions between unix timestamps and UTC calendar dates.
+
+use crate::export::error::ExportError;
+use crate::types::Timestamp;
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// Year, month and day of a day count since 1970-01-01, after Howard Hinnant's
+/// `civil_from_days`.
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Days since 1970-01-01 of a date from 1970 on, the inverse of `civil_from_days`.
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let month_index = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// `YYYY-MM-DD`.
+pub fn iso_date(timestamp: Timestamp) -> String {
+    let (year, month, day) = civil_from_days(timestamp / SECONDS_PER_DAY);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// `YYYY-MM-DDThh:mm:ssZ`.
+pub fn iso_date_time(timestamp: Timestamp) -> String {
+    let seconds = timestamp % SECONDS_PER_DAY;
+    format!(
+        "{}T{:02}:{:02}:{:02}Z",
+        iso_date(timestamp),
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// `YYMMDD`, as used by SWIFT messages.
+pub fn swift_date(timestamp: Timestamp) -> String {
+    let (year, month, day) = civil_from_days(timestamp / SECONDS_PER_DAY);
+    format!("{:02}{:02}{:02}", year % 100, month, day)
+}
+
+/// Start of the day given as `YYYY-MM-DD`, in UTC.
+pub fn parse_date(date: &str) -> Result<Timestamp, ExportError> {
+    let invalid = || ExportError::InvalidDate(date.to_string());
+    let mut parts = date.splitn(3, '-').map(|part| part.parse::<u64>());
+    let (Some(Ok(year)), Some(Ok(month)), Some(Ok(day))) =
+        (parts.next(), parts.next(), parts.next())
+    else {
+        return Err(invalid());
+    };
+    if year < 1970 || !(1..=12).contains(&month) || day == 0 {
+        return Err(invalid());
+    }
+    let days = days_from_civil(year, month, day);
+    // Rejects days past the end of the month, e.g. 2023-02-29
+    if civil_from_days(days) != (year, month, day) {
+        return Err(invalid());
+    }
+    Ok(days * SECONDS_PER_DAY)
+}
+
+/// Last second of the day given as `YYYY-MM-DD`, in UTC.
+pub fn parse_end_of_date(date: &str) -> Result<Timestamp, ExportError> {
+    Ok(parse_date(date)? + SECONDS_PER_DAY - 1)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn format_dates() {
+        assert_eq!(iso_date(0), "1970-01-01");
+        assert_eq!(iso_date_time(1_700_000_000), "2023-11-14T22:13:20Z");
+        assert_eq!(swift_date(1_709_164_800), "240229");
+    }
+
+    #[test]
+    fn parse_dates() {
+        assert_eq!(parse_date("2023-11-14").unwrap(), 1_699_920_000);
+        assert_eq!(parse_end_of_date("2023-11-14").unwrap(), 1_700_006_399);
+        assert_eq!(iso_date(parse_date("2024-02-29").unwrap()), "2024-02-29");
+        for date in [
+            "2023-02-29",
+            "2023-13-01",
+            "1969-12-31",
+            "2023-11",
+            "yesterday",
+        ] {
+            assert!(parse_date(date).is_err(), "{}", date);
+        }
+    }
+}
@@ -0,0 +1,360 @@
+use std::fmt::Write;
+
+use crate::export::calendar::{iso_date, iso_date_time};
+use crate::export::currency::{minor_units, round_to_minor_units};
+use crate::export::statement_options::StatementOptions;
+use crate::history::account_history::{HistoryEntry, PeriodActivity};
+use crate::transaction::Type;
+use crate::types::{Amount, ClientId};
+
+/// XML namespace of the documents written, version 08 of the message.
+pub const NAMESPACE: &str = "urn:iso:std:iso:20022:tech:xsd:camt.053.001.08";
+
+/// ISO 20022 `camt.053` bank to customer statement of one client's account.
+///
+/// Booked balances are the account total and the closing available balance its
+/// available funds. Deposits, withdrawals, chargebacks and their reversals are booked
+/// entries for the change of the total, fees included. Dispute holds do not change the
+/// total and are listed as information entries for the amount put on hold.
+pub fn camt053_document(
+    client_id: ClientId,
+    activity: &PeriodActivity,
+    options: &StatementOptions,
+) -> String {
+    let mut xml = String::new();
+    let identification = format!("STMT-{}-{}", client_id, options.created);
+    // Writing to a String can not fail
+    let _ = write_document(&mut xml, &identification, client_id, activity, options);
+    xml
+}
+
+fn write_document(
+    xml: &mut String,
+    identification: &str,
+    client_id: ClientId,
+    activity: &PeriodActivity,
+    options: &StatementOptions,
+) -> std::fmt::Result {
+    let created = iso_date_time(options.created);
+    writeln!(xml, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(xml, r#"<Document xmlns="{}">"#, NAMESPACE)?;
+    writeln!(xml, "  <BkToCstmrStmt>")?;
+    writeln!(xml, "    <GrpHdr>")?;
+    writeln!(xml, "      <MsgId>{}</MsgId>", identification)?;
+    writeln!(xml, "      <CreDtTm>{}</CreDtTm>", created)?;
+    writeln!(xml, "    </GrpHdr>")?;
+    writeln!(xml, "    <Stmt>")?;
+    writeln!(xml, "      <Id>{}</Id>", identification)?;
+    writeln!(xml, "      <CreDtTm>{}</CreDtTm>", created)?;
+    if let (Some(from), Some(to)) = (options.period.from, options.period.to) {
+        writeln!(xml, "      <FrToDt>")?;
+        writeln!(xml, "        <FrDtTm>{}</FrDtTm>", iso_date_time(from))?;
+        writeln!(xml, "        <ToDtTm>{}</ToDtTm>", iso_date_time(to))?;
+        writeln!(xml, "      </FrToDt>")?;
+    }
+    writeln!(xml, "      <Acct>")?;
+    writeln!(xml, "        <Id><Othr><Id>{}</Id></Othr></Id>", client_id)?;
+    writeln!(xml, "        <Ccy>{}</Ccy>", options.currency)?;
+    writeln!(xml, "      </Acct>")?;
+    let opening = options.opening_date();
+    let closing = options.closing_date();
+    write_balance(xml, "OPBD", activity.opening.total, opening, options)?;
+    write_balance(xml, "CLBD", activity.closing.total, closing, options)?;
+    write_balance(xml, "CLAV", activity.closing.available, closing, options)?;
+    for entry in &activity.entries {
+        write_entry(xml, entry, options)?;
+    }
+    writeln!(xml, "    </Stmt>")?;
+    writeln!(xml, "  </BkToCstmrStmt>")?;
+    writeln!(xml, "</Document>")
+}
+
+fn write_balance(
+    xml: &mut String,
+    code: &str,
+    amount: Amount,
+    date: u64,
+    options: &StatementOptions,
+) -> std::fmt::Result {
+    writeln!(xml, "      <Bal>")?;
+    writeln!(
+        xml,
+        "        <Tp><CdOrPrtry><Cd>{}</Cd></CdOrPrtry></Tp>",
+        code
+    )?;
+    write_amount(xml, "        ", amount, options)?;
+    writeln!(xml, "        <Dt><Dt>{}</Dt></Dt>", iso_date(date))?;
+    writeln!(xml, "      </Bal>")
+}
+
+/// `Amt` and `CdtDbtInd`: amounts are unsigned, in the minor units of the currency, and
+/// the indicator gives the sign.
+fn write_amount(
+    xml: &mut String,
+    indent: &str,
+    amount: Amount,
+    options: &StatementOptions,
+) -> std::fmt::Result {
+    let amount = round_to_minor_units(amount, &options.currency);
+    writeln!(
+        xml,
+        r#"{}<Amt Ccy="{}">{:.*}</Amt>"#,
+        indent,
+        options.currency,
+        minor_units(&options.currency) as usize,
+        amount.abs()
+    )?;
+    let indicator = if amount.is_sign_negative() && !amount.is_zero() {
+        "DBIT"
+    } else {
+        "CRDT"
+    };
+    writeln!(xml, "{}<CdtDbtInd>{}</CdtDbtInd>", indent, indicator)
+}
+
+fn write_entry(
+    xml: &mut String,
+    entry: &HistoryEntry,
+    options: &StatementOptions,
+) -> std::fmt::Result {
+    let transaction = &entry.transaction;
+    let (amount, status) = match transaction.t_type {
+        _ if entry.is_booking() => (entry.booked(), "BOOK"),
+        // The held amount leaves the available funds
+        Type::Dispute => (-entry.held(), "INFO"),
+        _ => return Ok(()),
+    };
+    if amount.is_zero() {
+        return Ok(());
+    }
+
+    writeln!(xml, "      <Ntry>")?;
+    writeln!(
+        xml,
+        "        <NtryRef>{}</NtryRef>",
+        transaction.transaction_id
+    )?;
+    write_amount(xml, "        ", amount, options)?;
+    writeln!(xml, "        <Sts><Cd>{}</Cd></Sts>", status)?;
+    if let Some(timestamp) = entry.position.timestamp {
+        writeln!(
+            xml,
+            "        <BookgDt><DtTm>{}</DtTm></BookgDt>",
+            iso_date_time(timestamp)
+        )?;
+    }
+    writeln!(
+        xml,
+        "        <BkTxCd><Prtry><Cd>{}</Cd></Prtry></BkTxCd>",
+        transaction.t_type.name().to_uppercase()
+    )?;
+    writeln!(
+        xml,
+        "        <AddtlNtryInf>{}</AddtlNtryInf>",
+        entry_information(entry)
+    )?;
+    writeln!(xml, "      </Ntry>")
+}
+
+/// Description of an entry for the account owner.
+pub(crate) fn entry_information(entry: &HistoryEntry) -> String {
+    let transaction = &entry.transaction;
+    let mut information = match transaction.t_type {
+        Type::Dispute => format!("Dispute hold on transaction {}", transaction.transaction_id),
+        Type::Chargeback => format!("Chargeback of transaction {}", transaction.transaction_id),
+        Type::ReverseChargeback => format!(
+            "Reversed chargeback of transaction {}",
+            transaction.transaction_id
+        ),
+        t_type => format!(
+            "{} {}",
+            capitalize(t_type.name()),
+            transaction.transaction_id
+        ),
+    };
+    if entry.after.locked && !entry.before.locked {
+        information.push_str(", account locked");
+    }
+    information
+}
+
+fn capitalize(name: &str) -> String {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use libxml::parser::Parser;
+    use libxml::schemas::{SchemaParserContext, SchemaValidationContext};
+    use rust_decimal::dec;
+
+    use super::*;
+    use crate::history::account_history::{Balances, Period};
+    use crate::transaction::Transaction;
+    use crate::types::EventPosition;
+
+    const SCHEMA: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/schemas/camt.053.001.08.xsd");
+
+    fn validate(xml: &str) {
+        let document = Parser::default().parse_string(xml).unwrap();
+        let mut schema = SchemaParserContext::from_file(SCHEMA);
+        let mut validation = SchemaValidationContext::from_parser(&mut schema).unwrap();
+        if let Err(errors) = validation.validate_document(&document) {
+            panic!(
+                "{:?}\n{}",
+                errors
+                    .iter()
+                    .map(|error| error.message.clone())
+                    .collect::<Vec<_>>(),
+                xml
+            );
+        }
+    }
+
+    pub fn entry(
+        t_type: Type,
+        transaction_id: u32,
+        timestamp: Option<u64>,
+        before: (Amount, Amount),
+        after: (Amount, Amount),
+    ) -> HistoryEntry {
+        let balances = |(available, held): (Amount, Amount)| Balances {
+            available,
+            held,
+            total: available + held,
+            locked: t_type == Type::Chargeback,
+        };
+        HistoryEntry {
+            position: EventPosition {
+                sequence: transaction_id as u64,
+                timestamp,
+            },
+            transaction: Transaction {
+                t_type,
+                t_client_id: 1,
+                transaction_id,
+                amount: None,
+                timestamp,
+            },
+            before: Balances {
+                locked: false,
+                ..balances(before)
+            },
+            after: balances(after),
+        }
+    }
+
+    pub fn history() -> Vec<HistoryEntry> {
+        vec![
+            entry(
+                Type::Deposit,
+                1,
+                Some(1_700_000_000),
+                (dec!(0), dec!(0)),
+                (dec!(10), dec!(0)),
+            ),
+            entry(
+                Type::Withdrawal,
+                2,
+                Some(1_700_000_100),
+                (dec!(10), dec!(0)),
+                (dec!(6), dec!(0)),
+            ),
+            entry(
+                Type::Dispute,
+                1,
+                Some(1_700_000_200),
+                (dec!(6), dec!(0)),
+                (dec!(-4), dec!(10)),
+            ),
+            entry(
+                Type::Chargeback,
+                1,
+                None,
+                (dec!(-4), dec!(10)),
+                (dec!(-4), dec!(0)),
+            ),
+        ]
+    }
+
+    #[test]
+    fn valid_statement() {
+        let history = history();
+        let options = StatementOptions::new("EUR", Period::default(), 1_700_086_400).unwrap();
+        let activity = PeriodActivity::of(&history, options.period, Balances::default());
+        let xml = camt053_document(1, &activity, &options);
+        validate(&xml);
+
+        assert_eq!(xml.matches("<Ntry>").count(), 4);
+        assert!(xml.contains(r#"<Amt Ccy="EUR">10.00</Amt>"#));
+        assert!(xml.contains("<Sts><Cd>INFO</Cd></Sts>"));
+        assert!(xml.contains("Chargeback of transaction 1, account locked"));
+        // Closing booked balance of -4
+        assert!(xml.contains(
+            "<Cd>CLBD</Cd></CdOrPrtry></Tp>\n        <Amt Ccy=\"EUR\">4.00</Amt>\n        <CdtDbtInd>DBIT</CdtDbtInd>"
+        ));
+    }
+
+    #[test]
+    fn amounts_in_minor_units() {
+        let amount = |value, currency| {
+            let options = StatementOptions::new(currency, Period::default(), 0).unwrap();
+            let mut xml = String::new();
+            write_amount(&mut xml, "", value, &options).unwrap();
+            xml
+        };
+
+        assert_eq!(
+            amount(dec!(-1.2345), "EUR"),
+            "<Amt Ccy=\"EUR\">1.23</Amt>\n<CdtDbtInd>DBIT</CdtDbtInd>\n"
+        );
+        assert!(amount(dec!(1.5), "JPY").starts_with(r#"<Amt Ccy="JPY">2</Amt>"#));
+        assert!(amount(dec!(1.2345), "KWD").starts_with(r#"<Amt Ccy="KWD">1.235</Amt>"#));
+        // Rounds to nothing, so it is no debit
+        assert!(amount(dec!(-0.0040), "EUR").ends_with("<CdtDbtInd>CRDT</CdtDbtInd>\n"));
+    }
+
+    #[test]
+    fn statement_for_period() {
+        let history = history();
+        let period = Period {
+            from: Some(1_700_000_050),
+            to: Some(1_700_000_150),
+        };
+        let options = StatementOptions::new("USD", period, 1_700_086_400).unwrap();
+        let activity = PeriodActivity::of(&history, period, Balances::default());
+        let xml = camt053_document(1, &activity, &options);
+        validate(&xml);
+
+        assert_eq!(xml.matches("<Ntry>").count(), 1);
+        assert!(xml.contains("<FrDtTm>2023-11-14T22:14:10Z</FrDtTm>"));
+        assert!(xml.contains("<NtryRef>2</NtryRef>"));
+    }
+
+    #[test]
+    fn empty_statement() {
+        let current = Balances {
+            available: dec!(1.5),
+            held: dec!(0),
+            total: dec!(1.5),
+            locked: false,
+        };
+        let options = StatementOptions::new("EUR", Period::default(), 0).unwrap();
+        let xml = camt053_document(
+            7,
+            &PeriodActivity::of(&[], options.period, current),
+            &options,
+        );
+        validate(&xml);
+        assert!(!xml.contains("<Ntry>"));
+    }
+
+    #[test]
+    fn invalid_currency() {
+        assert!(StatementOptions::new("euro", Period::default(), 0).is_err());
+    }
+}
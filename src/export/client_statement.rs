@@ -0,0 +1,409 @@
+use std::fmt::Write;
+
+use crate::export::calendar::{iso_date, iso_date_time};
+use crate::history::account_history::{Balances, HistoryEntry, Period, PeriodActivity};
+use crate::transaction::Type;
+use crate::types::{Amount, ClientId, TransactionId};
+
+/// Layouts of the readable statement.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StatementFormat {
+    Html,
+    Markdown,
+}
+
+impl StatementFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "html" => Some(Self::Html),
+            "markdown" => Some(Self::Markdown),
+            _ => None,
+        }
+    }
+}
+
+/// One step of a disputed transaction, in the order applied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DisputeStep<'a> {
+    pub entry: &'a HistoryEntry,
+    /// Funds moved by the step, always positive.
+    pub amount: Amount,
+    pub description: String,
+}
+
+/// Everything that happened to the dispute of one transaction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DisputeTimeline<'a> {
+    pub transaction_id: TransactionId,
+    pub steps: Vec<DisputeStep<'a>>,
+}
+
+impl DisputeTimeline<'_> {
+    /// Where the dispute stands after its last step.
+    pub fn status(&self) -> &'static str {
+        match self.steps.last().map(|step| step.entry.transaction.t_type) {
+            Some(Type::Dispute) => "open",
+            Some(Type::Resolve) => "resolved",
+            Some(Type::Chargeback) => "charged back",
+            Some(Type::ReverseChargeback) => "chargeback reversed",
+            _ => "unknown",
+        }
+    }
+}
+
+/// Statement of one client's account for support staff: the rows applied with the
+/// balances after each, the disputes and the lock status.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientStatement<'a> {
+    pub client_id: ClientId,
+    pub period: Period,
+    pub activity: PeriodActivity<'a>,
+}
+
+impl<'a> ClientStatement<'a> {
+    pub fn new(client_id: ClientId, period: Period, activity: PeriodActivity<'a>) -> Self {
+        Self {
+            client_id,
+            period,
+            activity,
+        }
+    }
+
+    /// Disputes of the period, in the order they were opened. A dispute opened before
+    /// the period starts with its first step within it.
+    pub fn disputes(&self) -> Vec<DisputeTimeline<'a>> {
+        let mut disputes: Vec<DisputeTimeline> = Vec::new();
+        for &entry in &self.activity.entries {
+            let transaction = &entry.transaction;
+            let (amount, description) = match transaction.t_type {
+                Type::Dispute => (entry.held(), "disputed, funds held"),
+                Type::Resolve => (-entry.held(), "resolved, funds released"),
+                Type::Chargeback => (-entry.booked(), "charged back, funds withdrawn"),
+                Type::ReverseChargeback => (entry.booked(), "chargeback reversed, funds restored"),
+                _ => continue,
+            };
+            let step = DisputeStep {
+                entry,
+                amount,
+                description: description.to_string() + lock_change(entry),
+            };
+            match disputes
+                .iter_mut()
+                .find(|dispute| dispute.transaction_id == transaction.transaction_id)
+            {
+                Some(dispute) => dispute.steps.push(step),
+                None => disputes.push(DisputeTimeline {
+                    transaction_id: transaction.transaction_id,
+                    steps: vec![step],
+                }),
+            }
+        }
+        disputes
+    }
+
+    /// The row that locked the account, when it is locked at the end of the period.
+    pub fn locked_by(&self) -> Option<&'a HistoryEntry> {
+        if !self.activity.closing.locked {
+            return None;
+        }
+        self.activity
+            .entries
+            .iter()
+            .rev()
+            .find(|entry| entry.after.locked && !entry.before.locked)
+            .copied()
+    }
+
+    pub fn render(&self, format: StatementFormat) -> String {
+        let mut text = String::new();
+        // Writing to a String can not fail
+        let _ = match format {
+            StatementFormat::Html => self.write_html(&mut text),
+            StatementFormat::Markdown => self.write_markdown(&mut text),
+        };
+        text
+    }
+
+    fn period_text(&self) -> String {
+        match (self.period.from, self.period.to) {
+            (None, None) => "all recorded activity".to_string(),
+            (from, to) => format!(
+                "{} to {}",
+                from.map_or("the start".to_string(), iso_date),
+                to.map_or("now".to_string(), iso_date)
+            ),
+        }
+    }
+
+    fn lock_text(&self) -> String {
+        match (self.activity.closing.locked, self.locked_by()) {
+            (false, _) => "active".to_string(),
+            (true, Some(entry)) => format!(
+                "locked by {} of transaction {}{}",
+                entry.transaction.t_type.name(),
+                entry.transaction.transaction_id,
+                when(entry).map_or(String::new(), |when| format!(" on {}", when))
+            ),
+            (true, None) => "locked".to_string(),
+        }
+    }
+
+    fn write_markdown(&self, text: &mut String) -> std::fmt::Result {
+        let activity = &self.activity;
+        writeln!(text, "# Statement of client {}", self.client_id)?;
+        writeln!(text)?;
+        writeln!(text, "- Period: {}", self.period_text())?;
+        writeln!(text, "- Account: {}", self.lock_text())?;
+        writeln!(text)?;
+        writeln!(text, "| Balance | Available | Held | Total |")?;
+        writeln!(text, "|---|--:|--:|--:|")?;
+        for (label, balances) in [("Opening", activity.opening), ("Closing", activity.closing)] {
+            writeln!(
+                text,
+                "| {} | {:.4} | {:.4} | {:.4} |",
+                label, balances.available, balances.held, balances.total
+            )?;
+        }
+        writeln!(text)?;
+
+        writeln!(text, "## Transactions")?;
+        writeln!(text)?;
+        if activity.entries.is_empty() {
+            writeln!(text, "No transactions.")?;
+        } else {
+            writeln!(
+                text,
+                "| # | Time | Type | Tx | Amount | Available | Held | Total | Note |"
+            )?;
+            writeln!(text, "|--:|---|---|--:|--:|--:|--:|--:|---|")?;
+            for entry in &activity.entries {
+                let [time, t_type, tx, amount, available, held, total, note] = row(entry);
+                writeln!(
+                    text,
+                    "| {} | {} | {} | {} | {} | {} | {} | {} | {} |",
+                    entry.position.sequence, time, t_type, tx, amount, available, held, total, note
+                )?;
+            }
+        }
+        writeln!(text)?;
+
+        writeln!(text, "## Disputes")?;
+        writeln!(text)?;
+        let disputes = self.disputes();
+        if disputes.is_empty() {
+            writeln!(text, "No disputes.")?;
+        }
+        for dispute in disputes {
+            writeln!(
+                text,
+                "### Transaction {} ({})",
+                dispute.transaction_id,
+                dispute.status()
+            )?;
+            writeln!(text)?;
+            for step in &dispute.steps {
+                writeln!(
+                    text,
+                    "- {}{:.4} {}",
+                    when(step.entry).map_or(String::new(), |when| when + ": "),
+                    step.amount,
+                    step.description
+                )?;
+            }
+            writeln!(text)?;
+        }
+        Ok(())
+    }
+
+    fn write_html(&self, text: &mut String) -> std::fmt::Result {
+        let activity = &self.activity;
+        writeln!(text, "<!DOCTYPE html>")?;
+        writeln!(text, r#"<html lang="en">"#)?;
+        writeln!(text, "<head>")?;
+        writeln!(text, r#"<meta charset="utf-8">"#)?;
+        writeln!(
+            text,
+            "<title>Statement of client {}</title>",
+            self.client_id
+        )?;
+        writeln!(
+            text,
+            "<style>table {{ border-collapse: collapse; }} th, td {{ border: 1px solid #ccc; padding: 2px 8px; }} td.amount {{ text-align: right; }} .locked {{ color: #b00; }}</style>"
+        )?;
+        writeln!(text, "</head>")?;
+        writeln!(text, "<body>")?;
+        writeln!(text, "<h1>Statement of client {}</h1>", self.client_id)?;
+        writeln!(text, "<ul>")?;
+        writeln!(text, "<li>Period: {}</li>", self.period_text())?;
+        let class = if activity.closing.locked {
+            r#" class="locked""#
+        } else {
+            ""
+        };
+        writeln!(
+            text,
+            "<li>Account: <span{}>{}</span></li>",
+            class,
+            self.lock_text()
+        )?;
+        writeln!(text, "</ul>")?;
+        writeln!(text, "<table>")?;
+        writeln!(
+            text,
+            "<tr><th>Balance</th><th>Available</th><th>Held</th><th>Total</th></tr>"
+        )?;
+        for (label, balances) in [("Opening", activity.opening), ("Closing", activity.closing)] {
+            writeln!(
+                text,
+                r#"<tr><td>{}</td><td class="amount">{:.4}</td><td class="amount">{:.4}</td><td class="amount">{:.4}</td></tr>"#,
+                label, balances.available, balances.held, balances.total
+            )?;
+        }
+        writeln!(text, "</table>")?;
+
+        writeln!(text, "<h2>Transactions</h2>")?;
+        if activity.entries.is_empty() {
+            writeln!(text, "<p>No transactions.</p>")?;
+        } else {
+            writeln!(text, "<table>")?;
+            writeln!(
+                text,
+                "<tr><th>#</th><th>Time</th><th>Type</th><th>Tx</th><th>Amount</th><th>Available</th><th>Held</th><th>Total</th><th>Note</th></tr>"
+            )?;
+            for entry in &activity.entries {
+                let [time, t_type, tx, amount, available, held, total, note] = row(entry);
+                writeln!(
+                    text,
+                    r#"<tr><td class="amount">{}</td><td>{}</td><td>{}</td><td class="amount">{}</td><td class="amount">{}</td><td class="amount">{}</td><td class="amount">{}</td><td class="amount">{}</td><td>{}</td></tr>"#,
+                    entry.position.sequence, time, t_type, tx, amount, available, held, total, note
+                )?;
+            }
+            writeln!(text, "</table>")?;
+        }
+
+        writeln!(text, "<h2>Disputes</h2>")?;
+        let disputes = self.disputes();
+        if disputes.is_empty() {
+            writeln!(text, "<p>No disputes.</p>")?;
+        }
+        for dispute in disputes {
+            writeln!(
+                text,
+                "<h3>Transaction {} ({})</h3>",
+                dispute.transaction_id,
+                dispute.status()
+            )?;
+            writeln!(text, "<ol>")?;
+            for step in &dispute.steps {
+                writeln!(
+                    text,
+                    "<li>{}{:.4} {}</li>",
+                    when(step.entry).map_or(String::new(), |when| when + ": "),
+                    step.amount,
+                    step.description
+                )?;
+            }
+            writeln!(text, "</ol>")?;
+        }
+        writeln!(text, "</body>")?;
+        writeln!(text, "</html>")
+    }
+}
+
+fn when(entry: &HistoryEntry) -> Option<String> {
+    entry.position.timestamp.map(iso_date_time)
+}
+
+fn lock_change(entry: &HistoryEntry) -> &'static str {
+    match (entry.before.locked, entry.after.locked) {
+        (false, true) => ", account locked",
+        (true, false) => ", account unlocked",
+        _ => "",
+    }
+}
+
+/// Cells of a transaction row: the row as read, then the balances after it. Amounts
+/// are those of the input, fees show in the balances.
+fn row(entry: &HistoryEntry) -> [String; 8] {
+    let transaction = &entry.transaction;
+    let Balances {
+        available,
+        held,
+        total,
+        ..
+    } = entry.after;
+    [
+        when(entry).unwrap_or_default(),
+        transaction.t_type.name().to_string(),
+        transaction.transaction_id.to_string(),
+        transaction
+            .amount
+            .map_or(String::new(), |amount| format!("{:.4}", amount)),
+        format!("{:.4}", available),
+        format!("{:.4}", held),
+        format!("{:.4}", total),
+        lock_change(entry).trim_start_matches(", ").to_string(),
+    ]
+}
+
+#[cfg(test)]
+pub mod tests {
+    use rust_decimal::dec;
+
+    use super::*;
+    use crate::export::camt053::tests::{entry, history};
+
+    #[test]
+    fn dispute_timeline() {
+        let mut history = history();
+        history.push(entry(
+            Type::ReverseChargeback,
+            1,
+            Some(1_700_000_400),
+            (dec!(-4), dec!(0)),
+            (dec!(6), dec!(0)),
+        ));
+        let activity = PeriodActivity::of(&history, Period::default(), Balances::default());
+        let statement = ClientStatement::new(1, Period::default(), activity);
+
+        let disputes = statement.disputes();
+        assert_eq!(disputes.len(), 1);
+        let amounts: Vec<Amount> = disputes[0].steps.iter().map(|step| step.amount).collect();
+        assert_eq!(amounts, vec![dec!(10), dec!(10), dec!(10)]);
+        assert_eq!(disputes[0].status(), "chargeback reversed");
+        assert_eq!(
+            disputes[0].steps[1].description,
+            "charged back, funds withdrawn, account locked"
+        );
+    }
+
+    #[test]
+    fn markdown_statement() {
+        let history = history();
+        let activity = PeriodActivity::of(&history, Period::default(), Balances::default());
+        let statement = ClientStatement::new(1, Period::default(), activity);
+        let markdown = statement.render(StatementFormat::Markdown);
+
+        assert!(markdown.starts_with("# Statement of client 1\n"));
+        assert!(markdown.contains("- Account: locked by chargeback of transaction 1\n"));
+        assert!(markdown.contains("| Closing | -4.0000 | 0.0000 | -4.0000 |"));
+        assert!(markdown.contains(
+            "| 2 | 2023-11-14T22:15:00Z | withdrawal | 2 |  | 6.0000 | 0.0000 | 6.0000 |  |"
+        ));
+        assert!(markdown.contains("### Transaction 1 (charged back)\n"));
+        assert!(markdown.contains("- 2023-11-14T22:16:40Z: 10.0000 disputed, funds held\n"));
+    }
+
+    #[test]
+    fn html_statement() {
+        let activity = PeriodActivity::of(&[], Period::default(), Balances::default());
+        let statement = ClientStatement::new(3, Period::default(), activity);
+        let html = statement.render(StatementFormat::Html);
+
+        assert!(html.starts_with("<!DOCTYPE html>\n"));
+        assert!(html.contains("<title>Statement of client 3</title>"));
+        assert!(html.contains("<span>active</span>"));
+        assert!(html.contains("<p>No transactions.</p>"));
+        assert!(html.ends_with("</html>\n"));
+    }
+}
@@ -0,0 +1,41 @@
+//! Minor units of ISO 4217 currencies, for formats whose amounts follow them.
+
+use rust_decimal::RoundingStrategy;
+
+use crate::types::Amount;
+
+/// Decimal places of `currency`. Most currencies have two.
+pub fn minor_units(currency: &str) -> u32 {
+    match currency {
+        "BIF" | "CLP" | "DJF" | "GNF" | "ISK" | "JPY" | "KMF" | "KRW" | "PYG" | "RWF" | "UGX"
+        | "UYI" | "VND" | "VUV" | "XAF" | "XOF" | "XPF" => 0,
+        "BHD" | "IQD" | "JOD" | "KWD" | "LYD" | "OMR" | "TND" => 3,
+        "CLF" | "UYW" => 4,
+        _ => 2,
+    }
+}
+
+/// `amount` rounded to the minor units of `currency`, halves away from zero.
+pub fn round_to_minor_units(amount: Amount, currency: &str) -> Amount {
+    amount.round_dp_with_strategy(
+        minor_units(currency),
+        RoundingStrategy::MidpointAwayFromZero,
+    )
+}
+
+#[cfg(test)]
+pub mod tests {
+    use rust_decimal::dec;
+
+    use super::*;
+
+    #[test]
+    fn round_amounts() {
+        assert_eq!(round_to_minor_units(dec!(1.2345), "EUR"), dec!(1.23));
+        assert_eq!(round_to_minor_units(dec!(1.0050), "EUR"), dec!(1.01));
+        assert_eq!(round_to_minor_units(dec!(-1.0050), "EUR"), dec!(-1.01));
+        assert_eq!(round_to_minor_units(dec!(1.5), "JPY"), dec!(2));
+        assert_eq!(round_to_minor_units(dec!(1.2345), "KWD"), dec!(1.235));
+        assert_eq!(round_to_minor_units(dec!(1.2345), "CLF"), dec!(1.2345));
+    }
+}
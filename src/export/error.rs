@@ -0,0 +1,13 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ExportError {
+    #[error("Error writing statement: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid date {0}, expected YYYY-MM-DD")]
+    InvalidDate(String),
+
+    #[error("Invalid currency {0}, expected an ISO 4217 code such as EUR")]
+    InvalidCurrency(String),
+}
@@ -0,0 +1,10 @@
+//! Account statements, for other systems and for support staff, built from the
+//! per-client history.
+
+pub mod calendar;
+pub mod camt053;
+pub mod client_statement;
+pub mod currency;
+pub mod error;
+pub mod mt940;
+pub mod statement_options;
@@ -0,0 +1,219 @@
+use std::fmt::Write;
+
+use crate::export::calendar::swift_date;
+use crate::export::camt053::entry_information;
+use crate::export::currency::{minor_units, round_to_minor_units};
+use crate::export::statement_options::StatementOptions;
+use crate::history::account_history::{HistoryEntry, PeriodActivity};
+use crate::transaction::Type;
+use crate::types::{Amount, ClientId, Timestamp};
+
+/// SWIFT MT940 customer statement of one client's account, the text block only.
+///
+/// Like `camt.053`, the booked balances are the account total and `:64:` the closing
+/// available balance. Only entries changing the total get a `:61:` line, dispute holds
+/// are left out. Amounts are rounded to the minor units of the currency: each entry is
+/// the change of the rounded running total, so the remainder of one rounding carries to
+/// the next and `:60F:` plus the entries always gives `:62F:`.
+pub fn mt940_statement(
+    client_id: ClientId,
+    activity: &PeriodActivity,
+    options: &StatementOptions,
+) -> String {
+    let mut text = String::new();
+    // Writing to a String can not fail
+    let _ = write_statement(&mut text, client_id, activity, options);
+    text
+}
+
+fn write_statement(
+    text: &mut String,
+    client_id: ClientId,
+    activity: &PeriodActivity,
+    options: &StatementOptions,
+) -> std::fmt::Result {
+    // At most 16 characters: 5 for the client and 10 for the time
+    write!(text, ":20:{}-{}\r\n", client_id, options.created)?;
+    write!(text, ":25:{}\r\n", client_id)?;
+    write!(text, ":28C:1\r\n")?;
+    write_balance(
+        text,
+        "60F",
+        activity.opening.total,
+        options.opening_date(),
+        options,
+    )?;
+    let mut total = activity.opening.total;
+    for entry in activity.entries.iter().filter(|entry| is_listed(entry)) {
+        let before = round_to_minor_units(total, &options.currency);
+        total += entry.booked();
+        let amount = round_to_minor_units(total, &options.currency) - before;
+        write_entry(text, entry, amount, options)?;
+    }
+    let closing = options.closing_date();
+    // The total the entries lead to, which is the closing total
+    write_balance(text, "62F", total, closing, options)?;
+    write_balance(text, "64", activity.closing.available, closing, options)?;
+    write!(text, "-\r\n")
+}
+
+fn write_balance(
+    text: &mut String,
+    tag: &str,
+    amount: Amount,
+    date: Timestamp,
+    options: &StatementOptions,
+) -> std::fmt::Result {
+    let amount = round_to_minor_units(amount, &options.currency);
+    write!(
+        text,
+        ":{}:{}{}{}{}\r\n",
+        tag,
+        mark(amount),
+        swift_date(date),
+        options.currency,
+        swift_amount(amount, &options.currency)
+    )
+}
+
+/// Whether `entry` gets a `:61:` line.
+fn is_listed(entry: &HistoryEntry) -> bool {
+    entry.is_booking() && !entry.booked().is_zero()
+}
+
+/// Writes `entry` with its rounded `amount`.
+fn write_entry(
+    text: &mut String,
+    entry: &HistoryEntry,
+    amount: Amount,
+    options: &StatementOptions,
+) -> std::fmt::Result {
+    let transaction = &entry.transaction;
+    let code = match transaction.t_type {
+        Type::Chargeback => "RTI",
+        Type::ReverseChargeback => "MSC",
+        _ => "TRF",
+    };
+    write!(
+        text,
+        ":61:{}{}{}N{}{}\r\n",
+        swift_date(entry.position.timestamp.unwrap_or(options.closing_date())),
+        mark(amount),
+        swift_amount(amount, &options.currency),
+        code,
+        transaction.transaction_id
+    )?;
+    write!(text, ":86:{}\r\n", entry_information(entry))
+}
+
+/// Debit/credit mark: amounts are unsigned, the mark gives the sign.
+fn mark(amount: Amount) -> &'static str {
+    if amount.is_sign_negative() && !amount.is_zero() {
+        "D"
+    } else {
+        "C"
+    }
+}
+
+/// Unsigned amount with a decimal comma, which SWIFT requires even without decimals.
+fn swift_amount(amount: Amount, currency: &str) -> String {
+    let units = minor_units(currency) as usize;
+    let amount = format!("{:.*}", units, amount.abs());
+    if units == 0 {
+        amount + ","
+    } else {
+        amount.replace('.', ",")
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use rust_decimal::dec;
+
+    use super::*;
+    use crate::export::camt053::tests::{entry, history};
+    use crate::history::account_history::{Balances, Period};
+
+    #[test]
+    fn statement() {
+        let history = history();
+        let options = StatementOptions::new("EUR", Period::default(), 1_700_086_400).unwrap();
+        let activity = PeriodActivity::of(&history, options.period, Balances::default());
+        assert_eq!(
+            mt940_statement(1, &activity, &options),
+            ":20:1-1700086400\r\n\
+             :25:1\r\n\
+             :28C:1\r\n\
+             :60F:C231115EUR0,00\r\n\
+             :61:231114C10,00NTRF1\r\n\
+             :86:Deposit 1\r\n\
+             :61:231114D4,00NTRF2\r\n\
+             :86:Withdrawal 2\r\n\
+             :61:231115D10,00NRTI1\r\n\
+             :86:Chargeback of transaction 1, account locked\r\n\
+             :62F:D231115EUR4,00\r\n\
+             :64:D231115EUR4,00\r\n\
+             -\r\n"
+        );
+    }
+
+    #[test]
+    fn rounded_to_minor_units() {
+        let history = vec![entry(
+            Type::Deposit,
+            3,
+            Some(1_700_000_000),
+            (dec!(0), dec!(0)),
+            (dec!(1.2345), dec!(0)),
+        )];
+        let period = Period::default();
+
+        let options = StatementOptions::new("JPY", period, 1_700_086_400).unwrap();
+        let activity = PeriodActivity::of(&history, period, Balances::default());
+        let text = mt940_statement(1, &activity, &options);
+        assert!(text.contains(":61:231114C1,NTRF3\r\n"));
+        assert!(text.contains(":62F:C231115JPY1,\r\n"));
+
+        let options = StatementOptions::new("KWD", period, 1_700_086_400).unwrap();
+        let text = mt940_statement(1, &activity, &options);
+        assert!(text.contains(":61:231114C1,235NTRF3\r\n"));
+    }
+
+    #[test]
+    fn entries_add_up_to_balances() {
+        // Three deposits of 0.005 would round to 0.01 each on their own, 0.03 in all
+        let history: Vec<HistoryEntry> = (0..3)
+            .map(|tx| {
+                let before = dec!(1.0000) + dec!(0.005) * Amount::from(tx);
+                entry(
+                    Type::Deposit,
+                    tx + 1,
+                    Some(1_700_000_000),
+                    (before, dec!(0)),
+                    (before + dec!(0.005), dec!(0)),
+                )
+            })
+            .collect();
+        let period = Period::default();
+        let options = StatementOptions::new("EUR", period, 1_700_086_400).unwrap();
+        let opening = Balances {
+            available: dec!(1.0000),
+            held: dec!(0),
+            total: dec!(1.0000),
+            locked: false,
+        };
+        let activity = PeriodActivity::of(&history, period, opening);
+        let text = mt940_statement(1, &activity, &options);
+        // 1.00 + 0.01 + 0.00 + 0.01 = 1.02
+        assert!(text.contains(
+            ":60F:C231115EUR1,00\r\n\
+             :61:231114C0,01NTRF1\r\n\
+             :86:Deposit 1\r\n\
+             :61:231114C0,00NTRF2\r\n\
+             :86:Deposit 2\r\n\
+             :61:231114C0,01NTRF3\r\n\
+             :86:Deposit 3\r\n\
+             :62F:C231115EUR1,02\r\n"
+        ));
+    }
+}
@@ -0,0 +1,36 @@
+use crate::export::error::ExportError;
+use crate::history::account_history::Period;
+use crate::types::Timestamp;
+
+/// What every statement export needs besides the account activity.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatementOptions {
+    /// ISO 4217 code of the currency the amounts are in.
+    pub currency: String,
+    pub period: Period,
+    /// When the statement is produced, unix seconds.
+    pub created: Timestamp,
+}
+
+impl StatementOptions {
+    pub fn new(currency: &str, period: Period, created: Timestamp) -> Result<Self, ExportError> {
+        if currency.len() != 3 || !currency.bytes().all(|byte| byte.is_ascii_uppercase()) {
+            return Err(ExportError::InvalidCurrency(currency.to_string()));
+        }
+        Ok(Self {
+            currency: currency.to_string(),
+            period,
+            created,
+        })
+    }
+
+    /// Date the opening balance is given for: the start of the period, if known.
+    pub fn opening_date(&self) -> Timestamp {
+        self.period.from.unwrap_or(self.created)
+    }
+
+    /// Date the closing balances are given for: the end of the period, if known.
+    pub fn closing_date(&self) -> Timestamp {
+        self.period.to.unwrap_or(self.created)
+    }
+}
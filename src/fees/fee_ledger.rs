@@ -0,0 +1,76 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::transaction::Type;
+use crate::types::{Amount, ClientId, TransactionId};
+
+/// A fee charged to a client, recorded next to the transaction that triggered it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeeEntry {
+    pub client_id: ClientId,
+    pub transaction_id: TransactionId,
+    pub t_type: Type,
+    pub amount: Amount,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FeeLedger {
+    entries: Vec<FeeEntry>,
+    revenue: Amount,
+}
+
+impl Default for FeeLedger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FeeLedger {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            revenue: Decimal::ZERO,
+        }
+    }
+
+    pub fn record(&mut self, entry: FeeEntry) {
+        self.revenue += entry.amount;
+        self.entries.push(entry);
+    }
+
+    pub fn entries(&self) -> &[FeeEntry] {
+        &self.entries
+    }
+
+    pub fn revenue(&self) -> Amount {
+        self.revenue
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use rust_decimal::dec;
+
+    use super::*;
+
+    #[test]
+    fn fee_ledger() {
+        let mut ledger = FeeLedger::new();
+
+        ledger.record(FeeEntry {
+            client_id: 1,
+            transaction_id: 1,
+            t_type: Type::Withdrawal,
+            amount: dec!(0.5),
+        });
+        ledger.record(FeeEntry {
+            client_id: 2,
+            transaction_id: 2,
+            t_type: Type::Chargeback,
+            amount: dec!(15),
+        });
+
+        assert_eq!(ledger.entries().len(), 2);
+        assert_eq!(ledger.revenue(), dec!(15.5));
+    }
+}
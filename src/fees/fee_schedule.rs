@@ -0,0 +1,168 @@
+use std::collections::{HashMap, HashSet};
+
+use rust_decimal::Decimal;
+use serde::Deserialize;
+
+use crate::transaction::Type;
+use crate::types::{Amount, ClientId};
+
+/// Tier used for clients that are not listed in any other tier.
+pub const DEFAULT_TIER: &str = "default";
+
+/// Fee charged for one transaction: a flat part plus a percentage of the amount.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Fee {
+    pub flat: Amount,
+    pub percent: Amount,
+}
+
+impl Fee {
+    pub fn is_negative(&self) -> bool {
+        self.flat < Decimal::ZERO || self.percent < Decimal::ZERO
+    }
+
+    pub fn apply(&self, amount: Amount) -> Amount {
+        (self.flat + amount * self.percent / Decimal::ONE_HUNDRED).round_dp(4)
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeeTier {
+    pub clients: HashSet<ClientId>,
+    pub deposit: Option<Fee>,
+    pub withdrawal: Option<Fee>,
+    pub chargeback: Option<Fee>,
+}
+
+/// Fees per client tier and transaction type, loaded from the `[fees]` config section.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeeSchedule {
+    pub tiers: HashMap<String, FeeTier>,
+}
+
+impl FeeSchedule {
+    pub fn is_empty(&self) -> bool {
+        self.tiers.is_empty()
+    }
+
+    /// Refuses fees that would credit the client, which the engine can only find out
+    /// after the transaction is applied, and clients listed in more than one tier,
+    /// whose tier would otherwise depend on map order.
+    pub fn validate(&self) -> Result<(), String> {
+        let mut listed = HashMap::new();
+        for (name, tier) in &self.tiers {
+            let fees = [tier.deposit, tier.withdrawal, tier.chargeback];
+            if fees.iter().flatten().any(Fee::is_negative) {
+                return Err(format!("negative fee in tier {}", name));
+            }
+            for client_id in &tier.clients {
+                if let Some(other) = listed.insert(*client_id, name) {
+                    return Err(format!(
+                        "client {} is listed in tiers {} and {}",
+                        client_id, other, name
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn tier_for(&self, client_id: ClientId) -> Option<&FeeTier> {
+        self.tiers
+            .values()
+            .find(|tier| tier.clients.contains(&client_id))
+            .or_else(|| self.tiers.get(DEFAULT_TIER))
+    }
+
+    /// Fee owed by `client_id` for a transaction of `t_type` over `amount`.
+    pub fn fee_for(&self, client_id: ClientId, t_type: &Type, amount: Amount) -> Amount {
+        let Some(tier) = self.tier_for(client_id) else {
+            return Decimal::ZERO;
+        };
+        let fee = match t_type {
+            Type::Deposit => tier.deposit,
+            Type::Withdrawal => tier.withdrawal,
+            Type::Chargeback => tier.chargeback,
+            _ => None,
+        };
+        fee.map_or(Decimal::ZERO, |fee| fee.apply(amount))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use rust_decimal::dec;
+
+    use super::*;
+
+    fn schedule() -> FeeSchedule {
+        toml::from_str(
+            r#"
+            [tiers.default]
+            withdrawal = { flat = 0.5, percent = 1 }
+            chargeback = { flat = 15 }
+
+            [tiers.premium]
+            clients = [7]
+            withdrawal = { percent = "0.25" }
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn fee_apply() {
+        let fee = Fee {
+            flat: dec!(0.5),
+            percent: dec!(1.5),
+        };
+        assert_eq!(fee.apply(dec!(100)), dec!(2.0000));
+        assert_eq!(fee.apply(dec!(0.0333)), dec!(0.5005));
+    }
+
+    #[test]
+    fn fee_for_tiers() {
+        let schedule = schedule();
+
+        assert_eq!(schedule.fee_for(1, &Type::Withdrawal, dec!(10)), dec!(0.6));
+        assert_eq!(schedule.fee_for(1, &Type::Chargeback, dec!(10)), dec!(15));
+        assert_eq!(schedule.fee_for(1, &Type::Deposit, dec!(10)), dec!(0));
+        assert_eq!(
+            schedule.fee_for(7, &Type::Withdrawal, dec!(10)),
+            dec!(0.025)
+        );
+        assert_eq!(schedule.fee_for(7, &Type::Chargeback, dec!(10)), dec!(0));
+    }
+
+    #[test]
+    fn validate_schedule() {
+        assert!(schedule().validate().is_ok());
+
+        let overlapping: FeeSchedule = toml::from_str(
+            r#"
+            [tiers.premium]
+            clients = [7]
+
+            [tiers.partner]
+            clients = [3, 7]
+            "#,
+        )
+        .unwrap();
+        assert!(overlapping.validate().unwrap_err().contains("client 7"));
+
+        let negative: FeeSchedule =
+            toml::from_str("[tiers.default]\nchargeback = { flat = -1 }").unwrap();
+        assert!(negative.validate().is_err());
+    }
+
+    #[test]
+    fn empty_schedule() {
+        let schedule = FeeSchedule::default();
+
+        assert!(schedule.is_empty());
+        assert_eq!(schedule.fee_for(1, &Type::Withdrawal, dec!(10)), dec!(0));
+    }
+}
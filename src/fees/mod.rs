@@ -0,0 +1,2 @@
+pub mod fee_ledger;
+pub mod fee_schedule;
@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::client::client_account::ClientAccount;
+use crate::transaction::{Transaction, Type};
+use crate::types::{Amount, ClientId, EventPosition, Timestamp};
+
+/// Balances of an account at one point in its history.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Balances {
+    pub available: Amount,
+    pub held: Amount,
+    pub total: Amount,
+    pub locked: bool,
+}
+
+impl Balances {
+    pub fn of(account: &ClientAccount) -> Self {
+        Self {
+            available: account.available(),
+            held: account.held(),
+            total: account.total(),
+            locked: account.locked(),
+        }
+    }
+}
+
+/// A row applied to a client's account, with the balances right before and after it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub position: EventPosition,
+    pub transaction: Transaction,
+    pub before: Balances,
+    pub after: Balances,
+}
+
+impl HistoryEntry {
+    /// Change of the total balance, fees included.
+    pub fn booked(&self) -> Amount {
+        self.after.total - self.before.total
+    }
+
+    /// Change of the held funds.
+    pub fn held(&self) -> Amount {
+        self.after.held - self.before.held
+    }
+
+    /// Whether the row moved money in or out of the account, as opposed to holds.
+    pub fn is_booking(&self) -> bool {
+        matches!(
+            self.transaction.t_type,
+            Type::Deposit | Type::Withdrawal | Type::Chargeback | Type::ReverseChargeback
+        )
+    }
+}
+
+/// Every row applied to each client's account, in the order they were applied.
+/// Recorded only when enabled with `[history]` in the configuration.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AccountHistory {
+    clients: HashMap<ClientId, Vec<HistoryEntry>>,
+}
+
+impl AccountHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, client_id: ClientId, entry: HistoryEntry) {
+        self.clients.entry(client_id).or_default().push(entry);
+    }
+
+    /// Clients that have a history, by id.
+    pub fn clients(&self) -> Vec<ClientId> {
+        let mut clients: Vec<ClientId> = self.clients.keys().copied().collect();
+        clients.sort();
+        clients
+    }
+
+    pub fn entries(&self, client_id: ClientId) -> &[HistoryEntry] {
+        self.clients
+            .get(&client_id)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+}
+
+/// A range of row timestamps, both ends included. Rows without a timestamp are only
+/// part of an unbounded period.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Period {
+    pub from: Option<Timestamp>,
+    pub to: Option<Timestamp>,
+}
+
+impl Period {
+    pub fn is_bounded(&self) -> bool {
+        self.from.is_some() || self.to.is_some()
+    }
+
+    pub fn contains(&self, position: &EventPosition) -> bool {
+        if !self.is_bounded() {
+            return true;
+        }
+        position.timestamp.is_some_and(|timestamp| {
+            self.from.is_none_or(|from| timestamp >= from)
+                && self.to.is_none_or(|to| timestamp <= to)
+        })
+    }
+
+    fn is_before(&self, position: &EventPosition) -> bool {
+        match (self.from, position.timestamp) {
+            (Some(from), Some(timestamp)) => timestamp < from,
+            // Untimed rows can not be placed, they count as earlier ones
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+}
+
+/// What happened to one account during a period.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeriodActivity<'a> {
+    pub opening: Balances,
+    pub closing: Balances,
+    pub entries: Vec<&'a HistoryEntry>,
+}
+
+impl<'a> PeriodActivity<'a> {
+    /// Activity of `entries` during `period`. `current` gives the balances when the
+    /// history holds no entry to take them from.
+    pub fn of(entries: &'a [HistoryEntry], period: Period, current: Balances) -> Self {
+        let within: Vec<&HistoryEntry> = entries
+            .iter()
+            .filter(|entry| period.contains(&entry.position))
+            .collect();
+        let opening = match entries
+            .iter()
+            .rev()
+            .find(|entry| period.is_before(&entry.position))
+        {
+            Some(entry) => entry.after,
+            None => entries.first().map_or(current, |entry| entry.before),
+        };
+        let closing = within.last().map_or(opening, |entry| entry.after);
+        Self {
+            opening,
+            closing,
+            entries: within,
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use rust_decimal::dec;
+
+    use super::*;
+
+    fn balances(total: Amount) -> Balances {
+        Balances {
+            available: total,
+            held: dec!(0),
+            total,
+            locked: false,
+        }
+    }
+
+    fn entry(transaction_id: u32, timestamp: u64, before: Amount, after: Amount) -> HistoryEntry {
+        HistoryEntry {
+            position: EventPosition {
+                sequence: transaction_id as u64,
+                timestamp: Some(timestamp),
+            },
+            transaction: Transaction {
+                t_type: Type::Deposit,
+                t_client_id: 1,
+                transaction_id,
+                amount: Some(after - before),
+                timestamp: Some(timestamp),
+            },
+            before: balances(before),
+            after: balances(after),
+        }
+    }
+
+    #[test]
+    fn period_activity() {
+        let mut history = AccountHistory::new();
+        history.record(1, entry(1, 100, dec!(0), dec!(10)));
+        history.record(1, entry(2, 200, dec!(10), dec!(15)));
+        history.record(1, entry(3, 300, dec!(15), dec!(16)));
+        assert_eq!(history.clients(), vec![1]);
+        assert!(history.entries(2).is_empty());
+
+        let period = Period {
+            from: Some(150),
+            to: Some(250),
+        };
+        let activity = PeriodActivity::of(history.entries(1), period, balances(dec!(16)));
+        assert_eq!(activity.opening.total, dec!(10));
+        assert_eq!(activity.closing.total, dec!(15));
+        assert_eq!(activity.entries.len(), 1);
+
+        // Nothing happened: closing is the opening
+        let period = Period {
+            from: Some(400),
+            to: None,
+        };
+        let activity = PeriodActivity::of(history.entries(1), period, balances(dec!(16)));
+        assert_eq!(activity.opening.total, dec!(16));
+        assert_eq!(activity.closing.total, dec!(16));
+        assert!(activity.entries.is_empty());
+
+        let activity = PeriodActivity::of(&[], Period::default(), balances(dec!(3)));
+        assert_eq!(activity.opening.total, dec!(3));
+    }
+}
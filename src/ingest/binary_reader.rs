@@ -0,0 +1,96 @@
+use std::io::{Read, Seek};
+
+use tokio::sync::mpsc;
+
+use crate::binary::record_reader::BinaryReader;
+use crate::ingest::csv_reader::ReadRow;
+use crate::ingest::error::ReadError;
+
+/// Sends the records of a binary input down `sender`. Positions are reported like CSV
+/// ones, the header counting as the first record, so checkpoints work the same way.
+pub fn read_binary_rows<R: Read + Seek>(
+    input: R,
+    resume_from: Option<csv::Position>,
+    sender: mpsc::Sender<ReadRow>,
+) {
+    let send_error = |err: ReadError, position: csv::Position| {
+        let _ = sender.blocking_send(ReadRow {
+            transaction: Err(err),
+            position,
+        });
+    };
+
+    let mut reader = match BinaryReader::new(input) {
+        Ok(reader) => reader,
+        Err(err) => return send_error(err.into(), csv::Position::new()),
+    };
+    if let Some(position) = resume_from
+        && let Err(err) = reader.seek(position.byte())
+    {
+        return send_error(err.into(), self::position(&reader));
+    }
+
+    while let Some(transaction) = reader.read() {
+        let transaction = transaction.map_err(ReadError::from);
+        let fatal = matches!(&transaction, Err(err) if err.is_fatal());
+        let row = ReadRow {
+            transaction,
+            position: position(&reader),
+        };
+        if sender.blocking_send(row).is_err() || fatal {
+            return;
+        }
+    }
+}
+
+fn position<R: Read>(reader: &BinaryReader<R>) -> csv::Position {
+    let mut position = csv::Position::new();
+    position
+        .set_byte(reader.byte())
+        .set_line(reader.record() + 1)
+        .set_record(reader.record() + 1);
+    position
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::io::Cursor;
+
+    use rust_decimal::dec;
+
+    use crate::binary::record_writer::BinaryWriter;
+    use crate::ingest::csv_reader::spawn_reader;
+    use crate::transaction::{Transaction, Type};
+
+    fn input(transaction_ids: &[u32]) -> Cursor<Vec<u8>> {
+        let mut writer = BinaryWriter::new(Vec::new()).unwrap();
+        for &transaction_id in transaction_ids {
+            writer
+                .write(&Transaction {
+                    t_type: Type::Deposit,
+                    t_client_id: 1,
+                    transaction_id,
+                    amount: Some(dec!(1.5)),
+                    timestamp: None,
+                })
+                .unwrap();
+        }
+        Cursor::new(writer.into_inner().unwrap())
+    }
+
+    #[tokio::test]
+    async fn read_binary_input() {
+        let mut receiver = spawn_reader(input(&[1, 2]), None, 8).unwrap();
+        let row = receiver.recv().await.unwrap();
+        assert_eq!(row.transaction.unwrap().transaction_id, 1);
+        assert_eq!(row.position.record(), 2);
+        assert_eq!(row.position.byte(), 24);
+
+        let position = receiver.recv().await.unwrap().position;
+        assert!(receiver.recv().await.is_none());
+
+        // Resuming after the last row reads nothing more
+        let mut receiver = spawn_reader(input(&[1, 2]), Some(position), 8).unwrap();
+        assert!(receiver.recv().await.is_none());
+    }
+}
@@ -0,0 +1,147 @@
+use std::io::{BufRead, BufReader, Read, Seek};
+
+use tokio::sync::mpsc;
+
+use crate::binary::record::MAGIC;
+use crate::ingest::binary_reader::read_binary_rows;
+use crate::ingest::error::ReadError;
+use crate::transaction::Transaction;
+
+/// A row read from the input, with the position right after it.
+#[derive(Debug)]
+pub struct ReadRow {
+    /// The row, or why it could not be read. An I/O error ends the input.
+    pub transaction: Result<Transaction, ReadError>,
+    pub position: csv::Position,
+}
+
+/// Builds the CSV reader every transactions input goes through.
+pub fn transactions_reader<R: Read>(reader: R) -> csv::Reader<R> {
+    csv::ReaderBuilder::new()
+        .trim(csv::Trim::All) //Whitespaces must be accepted
+        .delimiter(b',')
+        .flexible(true)
+        .from_reader(reader)
+}
+
+/// Reads `input` on a dedicated thread, so blocking reads and CSV parsing never hold a
+/// runtime worker, and hands the rows over through a channel of `capacity` rows. The
+/// thread waits while the channel is full, which slows the input down to the pace of
+/// the engine. Reading starts at `resume_from` when given. Inputs starting with the
+/// binary format magic are read as binary records instead of CSV.
+pub fn spawn_reader<R>(
+    input: R,
+    resume_from: Option<csv::Position>,
+    capacity: usize,
+) -> std::io::Result<mpsc::Receiver<ReadRow>>
+where
+    R: Read + Seek + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel(capacity);
+    std::thread::Builder::new()
+        .name("csv-reader".to_string())
+        .spawn(move || {
+            let mut input = BufReader::new(input);
+            match input.fill_buf() {
+                Ok(head) if head.starts_with(&MAGIC) => {
+                    read_binary_rows(input, resume_from, sender)
+                }
+                _ => read_rows(transactions_reader(input), resume_from, sender),
+            }
+        })?;
+    Ok(receiver)
+}
+
+fn read_rows<R: Read + Seek>(
+    mut reader: csv::Reader<R>,
+    resume_from: Option<csv::Position>,
+    sender: mpsc::Sender<ReadRow>,
+) {
+    let send_error = |err: csv::Error, position: &csv::Position| {
+        let _ = sender.blocking_send(ReadRow {
+            transaction: Err(err.into()),
+            position: position.clone(),
+        });
+    };
+
+    let headers = match reader.headers() {
+        Ok(headers) => headers.clone(),
+        Err(err) => return send_error(err, reader.position()),
+    };
+    if let Some(position) = resume_from
+        && let Err(err) = reader.seek(position)
+    {
+        return send_error(err, reader.position());
+    }
+
+    let mut record = csv::StringRecord::new();
+    loop {
+        let transaction = match reader.read_record(&mut record) {
+            Ok(true) => record.deserialize::<Transaction>(Some(&headers)),
+            Ok(false) => return,
+            Err(err) => Err(err),
+        };
+        let io_error = matches!(&transaction, Err(err) if err.is_io_error());
+        let row = ReadRow {
+            transaction: transaction.map_err(ReadError::from),
+            position: reader.position().clone(),
+        };
+        // The receiver is gone: nobody wants the rest of the input
+        if sender.blocking_send(row).is_err() || io_error {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::io::Cursor;
+
+    use rust_decimal::dec;
+
+    use super::*;
+    use crate::transaction::Type;
+
+    fn input(content: &str) -> Cursor<Vec<u8>> {
+        Cursor::new(content.as_bytes().to_vec())
+    }
+
+    #[tokio::test]
+    async fn read_rows_through_channel() {
+        let mut receiver = spawn_reader(
+            input("type, client, tx, amount\ndeposit, 1, 1, 1.5\nbogus, 1, 2, 1\n"),
+            None,
+            1,
+        )
+        .unwrap();
+
+        let row = receiver.recv().await.unwrap();
+        assert_eq!(
+            row.transaction.unwrap(),
+            Transaction {
+                t_type: Type::Deposit,
+                t_client_id: 1,
+                transaction_id: 1,
+                amount: Some(dec!(1.5)),
+                timestamp: None,
+            }
+        );
+        assert_eq!(row.position.record(), 2);
+
+        let row = receiver.recv().await.unwrap();
+        assert!(row.transaction.is_err());
+        assert!(receiver.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn resume_from_position() {
+        let content = "type,client,tx,amount\ndeposit,1,1,1.0\ndeposit,1,2,2.0\n";
+        let mut receiver = spawn_reader(input(content), None, 8).unwrap();
+        let position = receiver.recv().await.unwrap().position;
+
+        let mut receiver = spawn_reader(input(content), Some(position), 8).unwrap();
+        let row = receiver.recv().await.unwrap();
+        assert_eq!(row.transaction.unwrap().transaction_id, 2);
+        assert!(receiver.recv().await.is_none());
+    }
+}
@@ -0,0 +1,30 @@
+use thiserror::Error;
+
+use crate::binary::error::BinaryError;
+#[cfg(feature = "parquet")]
+use crate::columnar::error::ColumnarError;
+
+#[derive(Error, Debug)]
+pub enum ReadError {
+    #[error("{0}")]
+    Csv(#[from] csv::Error),
+
+    #[error("{0}")]
+    Binary(#[from] BinaryError),
+
+    #[cfg(feature = "parquet")]
+    #[error("{0}")]
+    Parquet(#[from] ColumnarError),
+}
+
+impl ReadError {
+    /// Whether the input can not be read any further, as opposed to one bad row.
+    pub fn is_fatal(&self) -> bool {
+        match self {
+            ReadError::Csv(err) => err.is_io_error(),
+            ReadError::Binary(err) => err.is_fatal(),
+            #[cfg(feature = "parquet")]
+            ReadError::Parquet(err) => err.is_fatal(),
+        }
+    }
+}
@@ -0,0 +1,5 @@
+pub mod binary_reader;
+pub mod csv_reader;
+pub mod error;
+#[cfg(feature = "parquet")]
+pub mod parquet_reader;
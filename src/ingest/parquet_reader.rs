@@ -0,0 +1,88 @@
+use std::path::PathBuf;
+
+use tokio::sync::mpsc;
+
+use crate::columnar::parquet_reader::ParquetTransactions;
+use crate::ingest::csv_reader::ReadRow;
+use crate::ingest::error::ReadError;
+
+/// Reads the Parquet file at `path` on a dedicated thread, like `spawn_reader` does for
+/// CSV and binary inputs. Positions count rows, the first row being record 2 as in CSV.
+pub fn spawn_parquet_reader(
+    path: impl Into<PathBuf>,
+    capacity: usize,
+) -> std::io::Result<mpsc::Receiver<ReadRow>> {
+    let path = path.into();
+    let (sender, receiver) = mpsc::channel(capacity);
+    std::thread::Builder::new()
+        .name("parquet-reader".to_string())
+        .spawn(move || {
+            let mut transactions = match ParquetTransactions::open(&path) {
+                Ok(transactions) => transactions,
+                Err(err) => {
+                    let _ = sender.blocking_send(ReadRow {
+                        transaction: Err(err.into()),
+                        position: csv::Position::new(),
+                    });
+                    return;
+                }
+            };
+            while let Some(transaction) = transactions.next() {
+                let transaction = transaction.map_err(ReadError::from);
+                let fatal = matches!(&transaction, Err(err) if err.is_fatal());
+                let mut position = csv::Position::new();
+                position
+                    .set_line(transactions.rows_read() + 1)
+                    .set_record(transactions.rows_read() + 1);
+                if sender
+                    .blocking_send(ReadRow {
+                        transaction,
+                        position,
+                    })
+                    .is_err()
+                    || fatal
+                {
+                    return;
+                }
+            }
+        })?;
+    Ok(receiver)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use rust_decimal::dec;
+
+    use super::*;
+    use crate::columnar::parquet_writer::OutcomeWriter;
+    use crate::transaction::{Transaction, Type};
+
+    #[tokio::test]
+    async fn read_parquet_input() {
+        // The outcome log has the transaction columns, so it can be read back as input
+        let path = std::env::temp_dir().join(format!("input_{}.parquet", std::process::id()));
+        let mut writer = OutcomeWriter::from_file(&path).unwrap();
+        for transaction_id in 1..=2 {
+            let transaction = Transaction {
+                t_type: Type::Deposit,
+                t_client_id: 1,
+                transaction_id,
+                amount: Some(dec!(1.5)),
+                timestamp: None,
+            };
+            writer.write(&transaction, "applied").unwrap();
+        }
+        writer.close().unwrap();
+
+        let mut receiver = spawn_parquet_reader(&path, 8).unwrap();
+        let row = receiver.recv().await.unwrap();
+        assert_eq!(row.transaction.unwrap().transaction_id, 1);
+        assert_eq!(row.position.record(), 2);
+        assert_eq!(
+            receiver.recv().await.unwrap().transaction.unwrap().amount,
+            Some(dec!(1.5))
+        );
+        assert!(receiver.recv().await.is_none());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,179 @@
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crate::compression::Compression;
+
+/// Input path standing for the standard input.
+pub const STDIN: &str = "-";
+
+/// A transactions source: a file, a named pipe or the standard input, each possibly
+/// gzip or zstd compressed. Pipes and the standard input may never end and can not seek.
+pub enum Input {
+    File(BufReader<std::fs::File>),
+    Compressed(CompressedFile),
+    Stdin(Box<dyn Read + Send>),
+}
+
+impl Input {
+    /// Opens `path`, or the standard input for `-`. Compression is recognised by the
+    /// `.gz` or `.zst` extension, or else by the magic bytes of the content.
+    pub fn open(path: &str) -> std::io::Result<Self> {
+        if path == STDIN {
+            let mut stdin = BufReader::new(std::io::stdin());
+            let compression = Compression::detect(&mut stdin)?;
+            return Ok(Input::Stdin(compression.decoder(stdin)?));
+        }
+
+        let mut file = BufReader::new(std::fs::File::open(path)?);
+        let compression = match Compression::from_extension(path) {
+            Compression::None => Compression::detect(&mut file)?,
+            compression => compression,
+        };
+        Ok(match compression {
+            Compression::None => Input::File(file),
+            compression => Input::Compressed(CompressedFile {
+                path: PathBuf::from(path),
+                compression,
+                decoder: compression.decoder(file)?,
+                position: 0,
+            }),
+        })
+    }
+
+    /// Whether `path` is a regular file, which can be fingerprinted and resumed.
+    pub fn is_regular_file(path: &str) -> bool {
+        path != STDIN && Path::new(path).metadata().is_ok_and(|m| m.is_file())
+    }
+}
+
+/// A compressed file. Offsets are positions in the decompressed stream, so seeking
+/// decompresses up to the target, from the start of the file if it lies behind.
+pub struct CompressedFile {
+    path: PathBuf,
+    compression: Compression,
+    decoder: Box<dyn Read + Send>,
+    position: u64,
+}
+
+impl CompressedFile {
+    fn seek_to(&mut self, target: u64) -> std::io::Result<u64> {
+        if target < self.position {
+            let file = BufReader::new(std::fs::File::open(&self.path)?);
+            self.decoder = self.compression.decoder(file)?;
+            self.position = 0;
+        }
+        let skip = target - self.position;
+        let skipped = std::io::copy(&mut (&mut self.decoder).take(skip), &mut std::io::sink())?;
+        self.position += skipped;
+        if skipped < skip {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "seek past the end of the compressed input",
+            ));
+        }
+        Ok(self.position)
+    }
+}
+
+impl Read for Input {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Input::File(file) => file.read(buf),
+            Input::Compressed(compressed) => {
+                let read = compressed.decoder.read(buf)?;
+                compressed.position += read as u64;
+                Ok(read)
+            }
+            Input::Stdin(stdin) => stdin.read(buf),
+        }
+    }
+}
+
+impl Seek for Input {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match (self, pos) {
+            (Input::File(file), pos) => file.seek(pos),
+            (Input::Compressed(compressed), SeekFrom::Start(target)) => compressed.seek_to(target),
+            (Input::Compressed(_), _) => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "compressed input can only seek from the start",
+            )),
+            (Input::Stdin(_), _) => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "standard input can not seek",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn open_missing_file() {
+        let err = Input::open("missing_transactions.csv").err().unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+        assert!(!Input::is_regular_file("missing_transactions.csv"));
+    }
+
+    #[test]
+    fn stdin_is_not_regular() {
+        assert!(!Input::is_regular_file(STDIN));
+        assert!(Input::is_regular_file("Cargo.toml"));
+    }
+
+    #[test]
+    fn compressed_file() {
+        let content = "type,client,tx,amount\ndeposit,1,1,1.0\ndeposit,1,2,2.0\n";
+        // Named without extension: recognised by its magic bytes
+        let path = std::env::temp_dir().join(format!("input_compressed_{}", std::process::id()));
+        std::fs::write(
+            &path,
+            Compression::Gzip.compress(content.as_bytes()).unwrap(),
+        )
+        .unwrap();
+        let path = path.to_str().unwrap().to_string();
+
+        let mut input = Input::open(&path).unwrap();
+        assert!(matches!(input, Input::Compressed(_)));
+        let mut read = String::new();
+        input.read_to_string(&mut read).unwrap();
+        assert_eq!(read, content);
+
+        // Seeking backwards starts over, seeking forwards skips
+        assert_eq!(input.seek(SeekFrom::Start(22)).unwrap(), 22);
+        let mut rest = String::new();
+        input.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "deposit,1,1,1.0\ndeposit,1,2,2.0\n");
+        assert!(input.seek(SeekFrom::End(0)).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn named_pipe() {
+        let path = std::env::temp_dir().join(format!("input_fifo_{}", std::process::id()));
+        let status = std::process::Command::new("mkfifo")
+            .arg(&path)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let path = path.to_str().unwrap().to_string();
+        assert!(!Input::is_regular_file(&path));
+
+        let writer_path = path.clone();
+        let writer = std::thread::spawn(move || {
+            std::fs::write(writer_path, "type,client,tx,amount\n").unwrap();
+        });
+        let mut content = String::new();
+        Input::open(&path)
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        writer.join().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(content, "type,client,tx,amount\n");
+    }
+}
@@ -0,0 +1,24 @@
+pub mod access;
+pub mod binary;
+pub mod client;
+#[cfg(feature = "parquet")]
+pub mod columnar;
+pub mod compression;
+pub mod config;
+pub mod engine;
+pub mod export;
+pub mod fees;
+pub mod history;
+pub mod ingest;
+pub mod input;
+pub mod observe;
+pub mod rejects;
+pub mod rules;
+pub mod seen_index;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod snapshot;
+pub mod storage;
+pub mod transaction;
+pub mod types;
+pub mod webhooks;
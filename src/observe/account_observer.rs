@@ -0,0 +1,10 @@
+use crate::engine::events::AccountChanged;
+
+/// Receives every change applied to an account.
+///
+/// Called by the engine while it still holds the account, so that each client's
+/// changes arrive in the order they were applied. Implementations must return quickly
+/// and never wait on the engine; hand the event over to another task for slow work.
+pub trait AccountObserver: Send + Sync {
+    fn account_changed(&self, event: &AccountChanged);
+}
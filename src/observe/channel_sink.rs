@@ -0,0 +1,34 @@
+use tokio::sync::broadcast;
+
+use crate::engine::events::AccountChanged;
+use crate::observe::account_observer::AccountObserver;
+
+/// In-process sink: every change is sent to the receivers of a broadcast channel.
+///
+/// Sending never blocks the engine. A receiver that falls more than `capacity` events
+/// behind loses the oldest ones and is told how many it missed with
+/// `RecvError::Lagged`. Changes published while nobody listens are dropped.
+#[derive(Debug, Clone)]
+pub struct ChannelSink {
+    sender: broadcast::Sender<AccountChanged>,
+}
+
+impl ChannelSink {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            sender: broadcast::channel(capacity).0,
+        }
+    }
+
+    /// A new receiver, getting the changes published from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<AccountChanged> {
+        self.sender.subscribe()
+    }
+}
+
+impl AccountObserver for ChannelSink {
+    fn account_changed(&self, event: &AccountChanged) {
+        // Fails only without receivers
+        let _ = self.sender.send(event.clone());
+    }
+}
@@ -0,0 +1,9 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ObserveError {
+    #[error("Error writing account changes: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Error encoding account change: {0}")]
+    Json(#[from] serde_json::Error),
+}
@@ -0,0 +1,146 @@
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::compression::{OutputFile, create_output};
+use crate::engine::events::AccountChanged;
+use crate::observe::account_observer::AccountObserver;
+use crate::observe::error::ObserveError;
+
+/// Writes every change as one JSON object per line.
+///
+/// Lines are buffered: `flush` once the input is done, or regularly for a feed read
+/// while the engine runs. Write errors are reported on stderr, the engine carries on.
+pub struct JsonlSink<W: Write + Send> {
+    writer: Mutex<std::io::BufWriter<W>>,
+}
+
+impl JsonlSink<OutputFile> {
+    /// Creates the file at `path`, compressed if it ends in `.gz` or `.zst`.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ObserveError> {
+        let (file, _) = create_output(path, false)?;
+        Ok(Self::from_writer(file))
+    }
+
+    /// Continues an existing file, e.g. the feed of an interrupted run being resumed.
+    pub fn append_file(path: impl AsRef<Path>) -> Result<Self, ObserveError> {
+        let (file, _) = create_output(path, true)?;
+        Ok(Self::from_writer(file))
+    }
+}
+
+impl<W: Write + Send> JsonlSink<W> {
+    pub fn from_writer(writer: W) -> Self {
+        Self {
+            writer: Mutex::new(std::io::BufWriter::new(writer)),
+        }
+    }
+
+    pub fn write(&self, event: &AccountChanged) -> Result<(), ObserveError> {
+        let mut writer = self.writer.lock().unwrap();
+        serde_json::to_writer(&mut *writer, event)?;
+        writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    pub fn flush(&self) -> Result<(), ObserveError> {
+        self.writer.lock().unwrap().flush()?;
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> Result<W, ObserveError> {
+        self.writer
+            .into_inner()
+            .unwrap()
+            .into_inner()
+            .map_err(|err| err.into_error().into())
+    }
+}
+
+impl<W: Write + Send> AccountObserver for JsonlSink<W> {
+    fn account_changed(&self, event: &AccountChanged) {
+        if let Err(err) = self.write(event) {
+            eprintln!("{}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use rust_decimal::dec;
+
+    use super::*;
+    use crate::history::account_history::Balances;
+    use crate::transaction::{Transaction, Type};
+    use crate::types::EventPosition;
+
+    #[test]
+    fn write_lines() {
+        let event = AccountChanged {
+            client_id: 1,
+            position: EventPosition {
+                sequence: 1,
+                timestamp: None,
+            },
+            transaction: Transaction {
+                t_type: Type::Deposit,
+                t_client_id: 1,
+                transaction_id: 1,
+                amount: Some(dec!(1.5)),
+                timestamp: None,
+            },
+            before: Balances::default(),
+            after: Balances {
+                available: dec!(1.5),
+                held: dec!(0),
+                total: dec!(1.5),
+                locked: false,
+            },
+        };
+        let sink = JsonlSink::from_writer(Vec::new());
+        sink.account_changed(&event);
+        sink.account_changed(&event);
+
+        let output = String::from_utf8(sink.into_inner().unwrap()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with(r#"{"client_id":1,"position":{"sequence":1,"timestamp":null},"transaction":{"type":"deposit","client":1,"tx":1,"amount":"1.5""#));
+        assert_eq!(
+            serde_json::from_str::<AccountChanged>(lines[1]).unwrap(),
+            event
+        );
+    }
+
+    #[test]
+    fn append_file_keeps_lines() {
+        let path = std::env::temp_dir().join(format!(
+            "payments_engine_jsonl_append_{}.jsonl",
+            std::process::id()
+        ));
+        let event = AccountChanged {
+            client_id: 2,
+            position: EventPosition {
+                sequence: 1,
+                timestamp: None,
+            },
+            transaction: Transaction {
+                t_type: Type::Deposit,
+                t_client_id: 2,
+                transaction_id: 1,
+                amount: Some(dec!(1)),
+                timestamp: None,
+            },
+            before: Balances::default(),
+            after: Balances::default(),
+        };
+        for _ in 0..2 {
+            let sink = JsonlSink::append_file(&path).unwrap();
+            sink.account_changed(&event);
+            sink.flush().unwrap();
+        }
+
+        let output = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(output.lines().count(), 2);
+    }
+}
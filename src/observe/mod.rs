@@ -0,0 +1,7 @@
+//! Change feed of the accounts: observers subscribed to the engine are told about every
+//! change applied to an account, in the order applied.
+
+pub mod account_observer;
+pub mod channel_sink;
+pub mod error;
+pub mod jsonl_sink;
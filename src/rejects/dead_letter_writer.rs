@@ -0,0 +1,95 @@
+use std::io::Write;
+use std::path::Path;
+
+use crate::compression::{OutputFile, create_output};
+use crate::rejects::error::RejectsError;
+use crate::transaction::Transaction;
+
+/// Dead-letter file: rows refused for a reason that may go away later, written in the
+/// input format so they can be fed back with the `retry` subcommand.
+pub struct DeadLetterWriter<W: Write> {
+    writer: csv::Writer<W>,
+}
+
+impl DeadLetterWriter<OutputFile> {
+    /// Creates the file at `path`, compressed if it ends in `.gz` or `.zst`.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, RejectsError> {
+        let (file, _) = create_output(path, false).map_err(csv::Error::from)?;
+        Ok(Self::from_writer(file))
+    }
+
+    /// Continues an existing file, writing the header only if it is empty.
+    pub fn append_file(path: impl AsRef<Path>) -> Result<Self, RejectsError> {
+        let (file, empty) = create_output(path, true).map_err(csv::Error::from)?;
+        Ok(Self {
+            writer: csv::WriterBuilder::new()
+                .has_headers(empty)
+                .from_writer(file),
+        })
+    }
+}
+
+impl<W: Write> DeadLetterWriter<W> {
+    pub fn from_writer(writer: W) -> Self {
+        Self {
+            writer: csv::Writer::from_writer(writer),
+        }
+    }
+
+    pub fn write(&mut self, transaction: &Transaction) -> Result<(), RejectsError> {
+        self.writer.serialize(transaction)?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), RejectsError> {
+        self.writer.flush().map_err(csv::Error::from)?;
+        Ok(())
+    }
+
+    pub fn into_inner(mut self) -> Result<W, RejectsError> {
+        self.flush()?;
+        self.writer
+            .into_inner()
+            .map_err(|err| csv::Error::from(err.into_error()).into())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use rust_decimal::dec;
+
+    use super::*;
+    use crate::transaction::Type;
+
+    #[test]
+    fn dead_letter_round_trip() {
+        let transactions = vec![
+            Transaction {
+                t_type: Type::Withdrawal,
+                t_client_id: 1,
+                transaction_id: 4,
+                amount: Some(dec!(1.5000)),
+                timestamp: Some(1_700_000_000),
+            },
+            Transaction {
+                t_type: Type::Dispute,
+                t_client_id: 2,
+                transaction_id: 9,
+                amount: None,
+                timestamp: None,
+            },
+        ];
+
+        let mut dead_letter_writer = DeadLetterWriter::from_writer(Vec::new());
+        for transaction in &transactions {
+            dead_letter_writer.write(transaction).unwrap();
+        }
+        let output = dead_letter_writer.into_inner().unwrap();
+
+        let read: Vec<Transaction> = csv::Reader::from_reader(&output[..])
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(read, transactions);
+    }
+}
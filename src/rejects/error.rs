@@ -0,0 +1,7 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum RejectsError {
+    #[error("Error writing rejects report: {0}")]
+    Write(#[from] csv::Error),
+}
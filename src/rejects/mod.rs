@@ -0,0 +1,3 @@
+pub mod dead_letter_writer;
+pub mod error;
+pub mod rejects_writer;
@@ -0,0 +1,121 @@
+use std::io::Write;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::compression::{OutputFile, create_output};
+use crate::engine::events::RowRejected;
+use crate::rejects::error::RejectsError;
+use crate::transaction::Type;
+use crate::types::{Amount, ClientId, TransactionId};
+
+#[derive(Serialize)]
+struct RejectRecord<'a> {
+    #[serde(rename = "type")]
+    t_type: Type,
+    client: ClientId,
+    tx: TransactionId,
+    amount: Option<Amount>,
+    reason: &'a str,
+}
+
+/// Rejects report: every refused row with the reason it was refused, as
+/// `type,client,tx,amount,reason` CSV.
+pub struct RejectsWriter<W: Write> {
+    writer: csv::Writer<W>,
+}
+
+impl RejectsWriter<OutputFile> {
+    /// Creates the file at `path`, compressed if it ends in `.gz` or `.zst`.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, RejectsError> {
+        let (file, _) = create_output(path, false).map_err(csv::Error::from)?;
+        Ok(Self::from_writer(file))
+    }
+
+    /// Continues an existing file, writing the header only if it is empty.
+    pub fn append_file(path: impl AsRef<Path>) -> Result<Self, RejectsError> {
+        let (file, empty) = create_output(path, true).map_err(csv::Error::from)?;
+        Ok(Self {
+            writer: csv::WriterBuilder::new()
+                .has_headers(empty)
+                .from_writer(file),
+        })
+    }
+}
+
+impl<W: Write> RejectsWriter<W> {
+    pub fn from_writer(writer: W) -> Self {
+        Self {
+            writer: csv::Writer::from_writer(writer),
+        }
+    }
+
+    pub fn write(&mut self, rejected: &RowRejected) -> Result<(), RejectsError> {
+        let transaction = &rejected.transaction;
+        self.writer.serialize(RejectRecord {
+            t_type: transaction.t_type,
+            client: transaction.t_client_id,
+            tx: transaction.transaction_id,
+            amount: transaction.amount,
+            reason: &rejected.reason,
+        })?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), RejectsError> {
+        self.writer.flush().map_err(csv::Error::from)?;
+        Ok(())
+    }
+
+    pub fn into_inner(mut self) -> Result<W, RejectsError> {
+        self.flush()?;
+        self.writer
+            .into_inner()
+            .map_err(|err| csv::Error::from(err.into_error()).into())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use rust_decimal::dec;
+
+    use super::*;
+    use crate::transaction::Transaction;
+
+    #[test]
+    fn write_rejects() {
+        let mut rejects_writer = RejectsWriter::from_writer(Vec::new());
+        rejects_writer
+            .write(&RowRejected {
+                transaction: Transaction {
+                    t_type: Type::Withdrawal,
+                    t_client_id: 1,
+                    transaction_id: 4,
+                    amount: Some(dec!(1.5000)),
+                    timestamp: None,
+                },
+                reason: "Client not found".to_string(),
+            })
+            .unwrap();
+        rejects_writer
+            .write(&RowRejected {
+                transaction: Transaction {
+                    t_type: Type::Dispute,
+                    t_client_id: 2,
+                    transaction_id: 9,
+                    amount: None,
+                    timestamp: None,
+                },
+                reason: "Transaction not found: 9".to_string(),
+            })
+            .unwrap();
+
+        let output = String::from_utf8(rejects_writer.into_inner().unwrap()).unwrap();
+        assert_eq!(
+            output,
+            "type,client,tx,amount,reason\n\
+             withdrawal,1,4,1.5000,Client not found\n\
+             dispute,2,9,,Transaction not found: 9\n"
+        );
+    }
+}
@@ -0,0 +1,2 @@
+pub mod risk_rule;
+pub mod rules_engine;
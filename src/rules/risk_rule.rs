@@ -0,0 +1,157 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::engine_config::Window;
+use crate::types::Amount;
+
+/// What happens to a row that triggers a rule. Ordered from the mildest to the strictest.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleAction {
+    /// Apply the row and only record the decision.
+    Flag,
+    /// Park the row for manual review.
+    Hold,
+    /// Refuse the row.
+    Reject,
+}
+
+/// A risk rule from the `[[rules]]` config section. Amount, count and sum rules look at
+/// deposits and withdrawals; count and sum compare against the client's earlier rows of
+/// the same type inside `window`.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum RiskRule {
+    MaxAmount {
+        max: Amount,
+        action: RuleAction,
+    },
+    MaxCount {
+        max: u64,
+        window: Window,
+        action: RuleAction,
+    },
+    MaxSum {
+        max: Amount,
+        window: Window,
+        action: RuleAction,
+    },
+    /// Disputes raised by a client divided by its deposits.
+    MaxDisputeRatio {
+        max: Amount,
+        action: RuleAction,
+    },
+}
+
+impl RiskRule {
+    pub fn action(&self) -> RuleAction {
+        match self {
+            RiskRule::MaxAmount { action, .. }
+            | RiskRule::MaxCount { action, .. }
+            | RiskRule::MaxSum { action, .. }
+            | RiskRule::MaxDisputeRatio { action, .. } => *action,
+        }
+    }
+
+    pub fn window(&self) -> Option<Window> {
+        match self {
+            RiskRule::MaxCount { window, .. } | RiskRule::MaxSum { window, .. } => Some(*window),
+            RiskRule::MaxAmount { .. } | RiskRule::MaxDisputeRatio { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for RiskRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RiskRule::MaxAmount { .. } => write!(f, "max_amount"),
+            RiskRule::MaxCount { .. } => write!(f, "max_count"),
+            RiskRule::MaxSum { .. } => write!(f, "max_sum"),
+            RiskRule::MaxDisputeRatio { .. } => write!(f, "max_dispute_ratio"),
+        }
+    }
+}
+
+impl fmt::Display for RuleAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleAction::Flag => write!(f, "flag"),
+            RuleAction::Hold => write!(f, "hold"),
+            RuleAction::Reject => write!(f, "reject"),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use rust_decimal::dec;
+
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Rules {
+        rules: Vec<RiskRule>,
+    }
+
+    #[test]
+    fn read_rules() {
+        let rules: Rules = toml::from_str(
+            r#"
+            [[rules]]
+            kind = "max_amount"
+            max = 1000
+            action = "reject"
+
+            [[rules]]
+            kind = "max_count"
+            max = 10
+            window = { events = 100 }
+            action = "hold"
+
+            [[rules]]
+            kind = "max_dispute_ratio"
+            max = 0.25
+            action = "flag"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            rules.rules,
+            vec![
+                RiskRule::MaxAmount {
+                    max: dec!(1000),
+                    action: RuleAction::Reject,
+                },
+                RiskRule::MaxCount {
+                    max: 10,
+                    window: Window::Events(100),
+                    action: RuleAction::Hold,
+                },
+                RiskRule::MaxDisputeRatio {
+                    max: dec!(0.25),
+                    action: RuleAction::Flag,
+                },
+            ]
+        );
+        assert_eq!(rules.rules[1].window(), Some(Window::Events(100)));
+        assert!(RuleAction::Reject > RuleAction::Hold);
+        assert!(RuleAction::Hold > RuleAction::Flag);
+    }
+
+    #[test]
+    fn read_invalid_rule() {
+        assert!(
+            toml::from_str::<Rules>(
+                r#"
+                [[rules]]
+                kind = "max_sum"
+                max = 10
+                action = "flag"
+                "#,
+            )
+            .is_err()
+        );
+    }
+}
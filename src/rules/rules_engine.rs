@@ -0,0 +1,393 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::config::engine_config::Window;
+use crate::rules::risk_rule::{RiskRule, RuleAction};
+use crate::transaction::{Transaction, Type};
+use crate::types::{Amount, ClientId, EventPosition, TransactionId};
+
+/// Decisions kept in the log. Older ones are dropped in batches once twice as many
+/// accumulated, and only counted.
+const DECISION_LOG_LIMIT: usize = 10_000;
+/// Rows a time window covers when the rows carry no timestamps to judge it by.
+const UNTIMED_WINDOW_EVENTS: u64 = 100_000;
+
+/// Outcome of a rule triggered by a row.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleDecision {
+    pub client_id: ClientId,
+    pub transaction_id: TransactionId,
+    pub rule: String,
+    pub action: RuleAction,
+    pub reason: String,
+}
+
+impl fmt::Display for RuleDecision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Risk rule {} ({}): client {}, transaction {}: {}",
+            self.rule, self.action, self.client_id, self.transaction_id, self.reason
+        )
+    }
+}
+
+/// A deposit or withdrawal kept for the sliding window rules.
+#[derive(Clone, Serialize, Deserialize)]
+struct WindowEntry {
+    position: EventPosition,
+    t_type: Type,
+    amount: Amount,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct ClientActivity {
+    window: VecDeque<WindowEntry>,
+    deposits: u64,
+    disputes: u64,
+}
+
+/// Evaluates the configured risk rules against each row before it is applied, and keeps
+/// the per-client activity they need together with a log of the latest decisions taken.
+/// Only the activity and the log are persisted; rules always come from the config.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RulesEngine {
+    #[serde(skip)]
+    rules: Vec<RiskRule>,
+    activity: HashMap<ClientId, ClientActivity>,
+    decisions: Vec<RuleDecision>,
+    /// Decisions dropped from the front of the log.
+    #[serde(default)]
+    dropped_decisions: u64,
+}
+
+impl Default for RulesEngine {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl RulesEngine {
+    pub fn new(rules: Vec<RiskRule>) -> Self {
+        Self {
+            rules,
+            activity: HashMap::new(),
+            decisions: Vec::new(),
+            dropped_decisions: 0,
+        }
+    }
+
+    /// Replaces the rules, keeping the recorded activity and decisions.
+    pub fn with_rules(mut self, rules: Vec<RiskRule>) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// The latest decisions, oldest first.
+    pub fn decisions(&self) -> &[RuleDecision] {
+        &self.decisions
+    }
+
+    /// Decisions taken so far, including those no longer in the log.
+    pub fn decision_count(&self) -> u64 {
+        self.dropped_decisions + self.decisions.len() as u64
+    }
+
+    /// The decisions in the log taken after the first `count`.
+    pub fn decisions_since(&self, count: u64) -> &[RuleDecision] {
+        let skip = count.saturating_sub(self.dropped_decisions) as usize;
+        &self.decisions[skip.min(self.decisions.len())..]
+    }
+
+    /// Runs every rule against `transaction` at `position`. Returns the decisions of the
+    /// triggered rules, which are also added to the decision log.
+    pub fn evaluate(
+        &mut self,
+        transaction: &Transaction,
+        position: EventPosition,
+    ) -> Vec<RuleDecision> {
+        if self.rules.is_empty() {
+            return Vec::new();
+        }
+
+        let activity = self.activity.get(&transaction.t_client_id);
+        let decisions: Vec<RuleDecision> = self
+            .rules
+            .iter()
+            .filter_map(|rule| {
+                check(rule, transaction, position, activity).map(|reason| RuleDecision {
+                    client_id: transaction.t_client_id,
+                    transaction_id: transaction.transaction_id,
+                    rule: rule.to_string(),
+                    action: rule.action(),
+                    reason,
+                })
+            })
+            .collect();
+
+        self.decisions.extend(decisions.iter().cloned());
+        if self.decisions.len() > 2 * DECISION_LOG_LIMIT {
+            let dropped = self.decisions.len() - DECISION_LOG_LIMIT;
+            self.decisions.drain(..dropped);
+            self.dropped_decisions += dropped as u64;
+        }
+        decisions
+    }
+
+    /// Adds a row that has been applied to the activity the rules look at.
+    pub fn record(&mut self, transaction: &Transaction, position: EventPosition) {
+        if self.rules.is_empty() {
+            return;
+        }
+
+        let activity = self.activity.entry(transaction.t_client_id).or_default();
+        match (transaction.t_type, transaction.amount) {
+            (t_type @ (Type::Deposit | Type::Withdrawal), Some(amount)) => {
+                if t_type == Type::Deposit {
+                    activity.deposits += 1;
+                }
+                activity.window.push_back(WindowEntry {
+                    position,
+                    t_type,
+                    amount,
+                });
+            }
+            (Type::Dispute, _) => activity.disputes += 1,
+            _ => {}
+        }
+
+        // Entries outside every window can not count towards any rule anymore
+        let rules = &self.rules;
+        while let Some(oldest) = activity.window.front() {
+            let expired = rules.iter().filter_map(RiskRule::window).all(|window| {
+                window.exceeded_or_after(UNTIMED_WINDOW_EVENTS, oldest.position, position)
+            });
+            if !expired {
+                break;
+            }
+            activity.window.pop_front();
+        }
+    }
+}
+
+/// Reason why `rule` triggers on `transaction`, if it does.
+fn check(
+    rule: &RiskRule,
+    transaction: &Transaction,
+    position: EventPosition,
+    activity: Option<&ClientActivity>,
+) -> Option<String> {
+    let t_type = transaction.t_type;
+    let amount = match t_type {
+        Type::Deposit | Type::Withdrawal => transaction.amount,
+        _ => None,
+    };
+    let in_window = |window: Window| {
+        activity
+            .into_iter()
+            .flat_map(|activity| activity.window.iter())
+            .filter(move |entry| {
+                entry.t_type == t_type
+                    && !window.exceeded_or_after(UNTIMED_WINDOW_EVENTS, entry.position, position)
+            })
+    };
+
+    match *rule {
+        RiskRule::MaxAmount { max, .. } => {
+            let amount = amount?;
+            (amount > max).then(|| format!("amount {:.4} exceeds {:.4}", amount, max))
+        }
+        RiskRule::MaxCount { max, window, .. } => {
+            amount?;
+            let count = in_window(window).count() as u64 + 1;
+            (count > max)
+                .then(|| format!("{} rows of this type within window exceed {}", count, max))
+        }
+        RiskRule::MaxSum { max, window, .. } => {
+            let sum = in_window(window).map(|entry| entry.amount).sum::<Amount>() + amount?;
+            (sum > max).then(|| format!("sum {:.4} within window exceeds {:.4}", sum, max))
+        }
+        RiskRule::MaxDisputeRatio { max, .. } => {
+            if t_type != Type::Dispute {
+                return None;
+            }
+            let activity = activity?;
+            if activity.deposits == 0 {
+                return None;
+            }
+            let ratio = Decimal::from(activity.disputes + 1) / Decimal::from(activity.deposits);
+            (ratio > max).then(|| format!("dispute ratio {:.4} exceeds {:.4}", ratio, max))
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use rust_decimal::dec;
+
+    use super::*;
+
+    fn row(t_type: Type, transaction_id: TransactionId, amount: Option<Amount>) -> Transaction {
+        Transaction {
+            t_type,
+            t_client_id: 1,
+            transaction_id,
+            amount,
+            timestamp: None,
+        }
+    }
+
+    fn position(sequence: u64) -> EventPosition {
+        EventPosition {
+            sequence,
+            timestamp: None,
+        }
+    }
+
+    #[test]
+    fn max_amount() {
+        let mut rules = RulesEngine::new(vec![RiskRule::MaxAmount {
+            max: dec!(100),
+            action: RuleAction::Reject,
+        }]);
+
+        assert!(
+            rules
+                .evaluate(&row(Type::Deposit, 1, Some(dec!(100))), position(1))
+                .is_empty()
+        );
+
+        let decisions = rules.evaluate(&row(Type::Withdrawal, 2, Some(dec!(100.5))), position(2));
+        assert_eq!(decisions.len(), 1);
+        assert_eq!(decisions[0].action, RuleAction::Reject);
+        assert_eq!(decisions[0].rule, "max_amount");
+        assert_eq!(decisions[0].reason, "amount 100.5000 exceeds 100.0000");
+        assert_eq!(rules.decisions(), &decisions[..]);
+    }
+
+    #[test]
+    fn max_count_sliding_window() {
+        let mut rules = RulesEngine::new(vec![RiskRule::MaxCount {
+            max: 2,
+            window: Window::Events(3),
+            action: RuleAction::Hold,
+        }]);
+
+        for sequence in [1, 2] {
+            let withdrawal = row(Type::Withdrawal, sequence as u32, Some(dec!(1)));
+            assert!(rules.evaluate(&withdrawal, position(sequence)).is_empty());
+            rules.record(&withdrawal, position(sequence));
+        }
+        // A deposit does not count towards withdrawals
+        assert!(
+            rules
+                .evaluate(&row(Type::Deposit, 3, Some(dec!(1))), position(3))
+                .is_empty()
+        );
+        assert_eq!(
+            rules
+                .evaluate(&row(Type::Withdrawal, 4, Some(dec!(1))), position(3))
+                .len(),
+            1
+        );
+        // Withdrawal 1 slid out of the window
+        assert!(
+            rules
+                .evaluate(&row(Type::Withdrawal, 5, Some(dec!(1))), position(5))
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn max_sum_by_time() {
+        let mut rules = RulesEngine::new(vec![RiskRule::MaxSum {
+            max: dec!(10),
+            window: Window::Seconds(60),
+            action: RuleAction::Flag,
+        }]);
+
+        let at = |sequence, timestamp| EventPosition {
+            sequence,
+            timestamp: Some(timestamp),
+        };
+        let deposit = row(Type::Deposit, 1, Some(dec!(6)));
+        rules.record(&deposit, at(1, 1_000));
+
+        let decisions = rules.evaluate(&row(Type::Deposit, 2, Some(dec!(5))), at(2, 1_030));
+        assert_eq!(
+            decisions[0].reason,
+            "sum 11.0000 within window exceeds 10.0000"
+        );
+        assert!(
+            rules
+                .evaluate(&row(Type::Deposit, 3, Some(dec!(5))), at(3, 1_061))
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn decision_log_is_capped() {
+        let mut rules = RulesEngine::new(vec![RiskRule::MaxAmount {
+            max: dec!(1),
+            action: RuleAction::Flag,
+        }]);
+        let rows = 2 * DECISION_LOG_LIMIT as u32 + 1;
+        for transaction_id in 1..=rows {
+            rules.evaluate(
+                &row(Type::Deposit, transaction_id, Some(dec!(2))),
+                position(transaction_id.into()),
+            );
+        }
+
+        assert_eq!(rules.decision_count(), u64::from(rows));
+        assert_eq!(rules.decisions().len(), DECISION_LOG_LIMIT);
+        assert_eq!(rules.decisions()[0].transaction_id, rows - 9_999);
+        let since = rules.decisions_since(rows as u64 - 2);
+        assert_eq!(since.len(), 2);
+        assert_eq!(since[0].transaction_id, rows - 1);
+        assert_eq!(rules.decisions_since(0).len(), DECISION_LOG_LIMIT);
+    }
+
+    #[test]
+    fn untimed_window_is_pruned() {
+        let mut rules = RulesEngine::new(vec![RiskRule::MaxCount {
+            max: 1,
+            window: Window::Seconds(60),
+            action: RuleAction::Flag,
+        }]);
+        rules.record(&row(Type::Deposit, 1, Some(dec!(1))), position(1));
+        let far = position(UNTIMED_WINDOW_EVENTS + 2);
+
+        assert!(
+            rules
+                .evaluate(&row(Type::Deposit, 2, Some(dec!(1))), far)
+                .is_empty()
+        );
+        rules.record(&row(Type::Deposit, 2, Some(dec!(1))), far);
+        assert_eq!(rules.activity[&1].window.len(), 1);
+    }
+
+    #[test]
+    fn max_dispute_ratio() {
+        let mut rules = RulesEngine::new(vec![RiskRule::MaxDisputeRatio {
+            max: dec!(0.5),
+            action: RuleAction::Reject,
+        }]);
+
+        for transaction_id in [1, 2] {
+            rules.record(
+                &row(Type::Deposit, transaction_id, Some(dec!(1))),
+                position(transaction_id as u64),
+            );
+        }
+        let dispute = row(Type::Dispute, 1, None);
+        assert!(rules.evaluate(&dispute, position(3)).is_empty());
+        rules.record(&dispute, position(3));
+
+        let decisions = rules.evaluate(&row(Type::Dispute, 2, None), position(4));
+        assert_eq!(decisions[0].reason, "dispute ratio 1.0000 exceeds 0.5000");
+    }
+}
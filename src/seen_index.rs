@@ -0,0 +1,160 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::types::TransactionId;
+
+/// Ids per chunk: the low 16 bits of a transaction id select the id inside its chunk.
+const CHUNK_BITS: u32 = 16;
+/// A chunk holding more ids than this is stored as a bitmap instead of a sorted list.
+const ARRAY_LIMIT: usize = 4096;
+const BITMAP_WORDS: usize = (1 << CHUNK_BITS) / 64;
+
+/// Ids of one chunk. Sparse chunks keep a sorted list of 2 byte ids, dense chunks a
+/// fixed 8 KiB bitmap, so memory stays bounded whatever the id distribution.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Chunk {
+    Array(Vec<u16>),
+    Bitmap(Vec<u64>),
+}
+
+impl Chunk {
+    fn contains(&self, low: u16) -> bool {
+        match self {
+            Chunk::Array(ids) => ids.binary_search(&low).is_ok(),
+            Chunk::Bitmap(words) => words[low as usize / 64] & (1 << (low % 64)) != 0,
+        }
+    }
+
+    /// Adds `low`, returning whether it was not present yet.
+    fn insert(&mut self, low: u16) -> bool {
+        match self {
+            Chunk::Array(ids) => {
+                let Err(index) = ids.binary_search(&low) else {
+                    return false;
+                };
+                if ids.len() < ARRAY_LIMIT {
+                    ids.insert(index, low);
+                    return true;
+                }
+                let mut words = vec![0u64; BITMAP_WORDS];
+                for &id in ids.iter() {
+                    words[id as usize / 64] |= 1 << (id % 64);
+                }
+                *self = Chunk::Bitmap(words);
+                self.insert(low)
+            }
+            Chunk::Bitmap(words) => {
+                let word = &mut words[low as usize / 64];
+                let bit = 1 << (low % 64);
+                let inserted = *word & bit == 0;
+                *word |= bit;
+                inserted
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Chunk::Array(ids) => ids.len(),
+            Chunk::Bitmap(words) => words.iter().map(|word| word.count_ones() as usize).sum(),
+        }
+    }
+}
+
+/// Every transaction id that moved money (deposits and withdrawals), kept across runs
+/// so a replayed feed is applied exactly once.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SeenIndex {
+    chunks: BTreeMap<u16, Chunk>,
+}
+
+impl SeenIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn contains(&self, transaction_id: TransactionId) -> bool {
+        let (high, low) = split(transaction_id);
+        self.chunks
+            .get(&high)
+            .is_some_and(|chunk| chunk.contains(low))
+    }
+
+    /// Marks `transaction_id` as seen, returning whether it was not seen before.
+    pub fn insert(&mut self, transaction_id: TransactionId) -> bool {
+        let (high, low) = split(transaction_id);
+        self.chunks
+            .entry(high)
+            .or_insert_with(|| Chunk::Array(Vec::new()))
+            .insert(low)
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.values().map(Chunk::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+}
+
+fn split(transaction_id: TransactionId) -> (u16, u16) {
+    ((transaction_id >> CHUNK_BITS) as u16, transaction_id as u16)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn seen_index() {
+        let mut seen_index = SeenIndex::new();
+        assert!(seen_index.is_empty());
+
+        assert!(seen_index.insert(1));
+        assert!(seen_index.insert(u32::MAX));
+        assert!(!seen_index.insert(1));
+
+        assert!(seen_index.contains(1));
+        assert!(seen_index.contains(u32::MAX));
+        assert!(!seen_index.contains(2));
+        assert!(!seen_index.contains(1 << CHUNK_BITS | 1));
+        assert_eq!(seen_index.len(), 2);
+    }
+
+    #[test]
+    fn dense_chunk_becomes_bitmap() {
+        let mut seen_index = SeenIndex::new();
+        for transaction_id in (0..2 * ARRAY_LIMIT as u32).step_by(2) {
+            assert!(seen_index.insert(transaction_id));
+        }
+        assert!(matches!(seen_index.chunks[&0], Chunk::Array(_)));
+
+        assert!(seen_index.insert(1));
+        assert!(matches!(seen_index.chunks[&0], Chunk::Bitmap(_)));
+        assert_eq!(seen_index.len(), ARRAY_LIMIT + 1);
+        assert!(seen_index.contains(1));
+        assert!(seen_index.contains(2 * ARRAY_LIMIT as u32 - 2));
+        assert!(!seen_index.contains(3));
+        assert!(!seen_index.insert(4));
+    }
+
+    #[test]
+    fn seen_index_serde() {
+        let mut seen_index = SeenIndex::new();
+        seen_index.insert(7);
+        seen_index.insert(70_000);
+
+        let json = serde_json::to_string(&seen_index).unwrap();
+        assert_eq!(
+            json,
+            r#"{"chunks":{"0":{"array":[7]},"1":{"array":[4464]}}}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<SeenIndex>(&json).unwrap(),
+            seen_index
+        );
+    }
+}
@@ -0,0 +1,294 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use axum::extract::ws::{Message, WebSocket};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::engine::events::AccountChanged;
+use crate::engine::payments_engine::PaymentsEngine;
+use crate::history::account_history::Balances;
+use crate::transaction::Type;
+use crate::types::{Amount, ClientId, Timestamp, TransactionId};
+
+/// Longest a message may take to be accepted by the socket. A connection that stalls
+/// longer is closed.
+pub const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The clients a request is about.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Selection {
+    All(AllClients),
+    Clients(Vec<ClientId>),
+}
+
+/// The `"all"` keyword.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AllClients {
+    All,
+}
+
+/// Messages a socket accepts: `{"subscribe": [1, 2]}`, `{"subscribe": "all"}`,
+/// `{"unsubscribe": [1]}` or `{"unsubscribe": "all"}`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SocketRequest {
+    Subscribe(Selection),
+    Unsubscribe(Selection),
+}
+
+/// Clients a socket follows. Unsubscribing some clients does not narrow a
+/// subscription to all of them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Subscription {
+    all: bool,
+    clients: HashSet<ClientId>,
+}
+
+impl Subscription {
+    pub fn apply(&mut self, request: &SocketRequest) {
+        match request {
+            SocketRequest::Subscribe(Selection::All(_)) => self.all = true,
+            SocketRequest::Subscribe(Selection::Clients(clients)) => {
+                self.clients.extend(clients.iter().copied())
+            }
+            SocketRequest::Unsubscribe(Selection::All(_)) => *self = Self::default(),
+            SocketRequest::Unsubscribe(Selection::Clients(clients)) => {
+                for client_id in clients {
+                    self.clients.remove(client_id);
+                }
+            }
+        }
+    }
+
+    pub fn contains(&self, client_id: ClientId) -> bool {
+        self.all || self.clients.contains(&client_id)
+    }
+}
+
+/// Messages sent to a socket, JSON objects tagged with their `type`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SocketMessage {
+    /// Current balances of a client, sent on subscribing and after missed changes.
+    Balance {
+        client: ClientId,
+        #[serde(flatten)]
+        balances: Balances,
+    },
+    /// A row changed the client's account: the balances after it and the changes.
+    Delta {
+        client: ClientId,
+        tx: TransactionId,
+        cause: Type,
+        timestamp: Option<Timestamp>,
+        #[serde(flatten)]
+        balances: Balances,
+        available_change: Amount,
+        held_change: Amount,
+        total_change: Amount,
+    },
+    /// The account got locked, or unlocked by a chargeback reversal.
+    Lock {
+        client: ClientId,
+        tx: TransactionId,
+        cause: Type,
+        locked: bool,
+    },
+    /// Changes were dropped because the socket fell behind; the balances follow.
+    Lagged {
+        missed: u64,
+    },
+    Error {
+        message: String,
+    },
+}
+
+impl SocketMessage {
+    /// The delta for `change`, followed by a lock event if the lock status changed.
+    pub fn from_change(change: &AccountChanged) -> Vec<Self> {
+        let transaction = &change.transaction;
+        let mut messages = vec![SocketMessage::Delta {
+            client: change.client_id,
+            tx: transaction.transaction_id,
+            cause: transaction.t_type,
+            timestamp: change.position.timestamp,
+            balances: change.after,
+            available_change: change.after.available - change.before.available,
+            held_change: change.after.held - change.before.held,
+            total_change: change.after.total - change.before.total,
+        }];
+        if change.after.locked != change.before.locked {
+            messages.push(SocketMessage::Lock {
+                client: change.client_id,
+                tx: transaction.transaction_id,
+                cause: transaction.t_type,
+                locked: change.after.locked,
+            });
+        }
+        messages
+    }
+}
+
+/// Serves one socket: applies its requests and forwards the changes of the clients it
+/// follows. `changes` is read on its own, so a stalled socket never holds the engine
+/// back; it only misses changes, and then gets the current balances instead.
+///
+/// A delta may repeat a change already included in the balances sent on subscribing;
+/// the balances it carries are still the right ones.
+pub async fn balance_socket(
+    mut socket: WebSocket,
+    payments_engine: PaymentsEngine,
+    mut changes: broadcast::Receiver<AccountChanged>,
+) {
+    let mut subscription = Subscription::default();
+    loop {
+        let sent = tokio::select! {
+            request = socket.recv() => match request {
+                Some(Ok(Message::Text(text))) => match serde_json::from_str::<SocketRequest>(&text) {
+                    Ok(request) => {
+                        subscription.apply(&request);
+                        // Newly followed clients start from their current balances
+                        let mut added = Subscription::default();
+                        added.apply(&request);
+                        send_balances(&mut socket, &payments_engine, &added).await
+                    }
+                    Err(err) => {
+                        send(&mut socket, &SocketMessage::Error { message: err.to_string() }).await
+                    }
+                },
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                // Pings are answered by the socket itself
+                Some(Ok(_)) => Ok(()),
+            },
+            change = changes.recv() => match change {
+                Ok(change) if subscription.contains(change.client_id) => {
+                    send_all(&mut socket, SocketMessage::from_change(&change)).await
+                }
+                Ok(_) => Ok(()),
+                Err(RecvError::Lagged(missed)) => {
+                    match send(&mut socket, &SocketMessage::Lagged { missed }).await {
+                        Ok(()) => send_balances(&mut socket, &payments_engine, &subscription).await,
+                        err => err,
+                    }
+                }
+                Err(RecvError::Closed) => return,
+            },
+        };
+        if sent.is_err() {
+            return;
+        }
+    }
+}
+
+async fn send_balances(
+    socket: &mut WebSocket,
+    payments_engine: &PaymentsEngine,
+    subscription: &Subscription,
+) -> Result<(), ()> {
+    let balances = payments_engine
+        .accounts()
+        .await
+        .into_iter()
+        .filter(|(client_id, _)| subscription.contains(*client_id))
+        .map(|(client, account)| SocketMessage::Balance {
+            client,
+            balances: Balances::of(&account),
+        });
+    send_all(socket, balances).await
+}
+
+async fn send_all(
+    socket: &mut WebSocket,
+    messages: impl IntoIterator<Item = SocketMessage>,
+) -> Result<(), ()> {
+    for message in messages {
+        send(socket, &message).await?;
+    }
+    Ok(())
+}
+
+async fn send(socket: &mut WebSocket, message: &SocketMessage) -> Result<(), ()> {
+    // The messages hold no maps with non-string keys, encoding can not fail
+    let text = serde_json::to_string(message).unwrap();
+    match tokio::time::timeout(SEND_TIMEOUT, socket.send(Message::Text(text.into()))).await {
+        Ok(Ok(())) => Ok(()),
+        _ => Err(()),
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use rust_decimal::dec;
+
+    use super::*;
+    use crate::transaction::Transaction;
+    use crate::types::EventPosition;
+
+    #[test]
+    fn requests() {
+        let mut subscription = Subscription::default();
+        let request: SocketRequest = serde_json::from_str(r#"{"subscribe": [1, 2]}"#).unwrap();
+        subscription.apply(&request);
+        assert!(subscription.contains(2));
+        assert!(!subscription.contains(3));
+
+        let request: SocketRequest = serde_json::from_str(r#"{"unsubscribe": [2]}"#).unwrap();
+        subscription.apply(&request);
+        assert!(!subscription.contains(2));
+
+        let request: SocketRequest = serde_json::from_str(r#"{"subscribe": "all"}"#).unwrap();
+        subscription.apply(&request);
+        assert!(subscription.contains(3));
+
+        let request: SocketRequest = serde_json::from_str(r#"{"unsubscribe": "all"}"#).unwrap();
+        subscription.apply(&request);
+        assert!(!subscription.contains(1));
+
+        assert!(serde_json::from_str::<SocketRequest>(r#"{"subscribe": "some"}"#).is_err());
+    }
+
+    #[test]
+    fn change_messages() {
+        let locked = Balances {
+            available: dec!(-1),
+            held: dec!(0),
+            total: dec!(-1),
+            locked: true,
+        };
+        let change = AccountChanged {
+            client_id: 4,
+            position: EventPosition {
+                sequence: 9,
+                timestamp: Some(1_700_000_000),
+            },
+            transaction: Transaction {
+                t_type: Type::Chargeback,
+                t_client_id: 4,
+                transaction_id: 7,
+                amount: None,
+                timestamp: Some(1_700_000_000),
+            },
+            before: Balances {
+                available: dec!(-1),
+                held: dec!(5),
+                total: dec!(4),
+                locked: false,
+            },
+            after: locked,
+        };
+        let messages = SocketMessage::from_change(&change);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(
+            serde_json::to_string(&messages[0]).unwrap(),
+            r#"{"type":"delta","client":4,"tx":7,"cause":"chargeback","timestamp":1700000000,"available":"-1","held":"0","total":"-1","locked":true,"available_change":"0","held_change":"-5","total_change":"-5"}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&messages[1]).unwrap(),
+            r#"{"type":"lock","client":4,"tx":7,"cause":"chargeback","locked":true}"#
+        );
+    }
+}
@@ -0,0 +1,458 @@
+use std::future::Future;
+use std::str::FromStr;
+
+use tokio::net::TcpListener;
+use tonic::service::Interceptor;
+use tonic::service::interceptor::InterceptedService;
+use tonic::transport::Server;
+use tonic::transport::server::TcpIncoming;
+use tonic::{Code, Request, Response, Status, Streaming};
+
+use crate::client::error::ClientAccountError;
+use crate::config::engine_config::ServerConfig;
+use crate::engine::error::EngineError;
+use crate::engine::payments_engine::PaymentsEngine;
+use crate::history::account_history::Balances;
+use crate::transaction::{Transaction, Type, validate_amount};
+use crate::types::{Amount, ClientId};
+
+/// Messages and service generated from `schemas/payments.proto`.
+pub mod proto {
+    tonic::include_proto!("payments");
+}
+
+use proto::payments_server::{Payments, PaymentsServer};
+use proto::{
+    Account, GetAccountRequest, ListAccountsReply, ListAccountsRequest, Outcome, Refusal,
+    StreamSummary, TransactionReply, TransactionRequest, TransactionType,
+};
+
+/// Refused rows a stream summary lists; the rest are only counted, so that a long
+/// stream can not grow the summary without bound.
+pub const LISTED_REFUSALS: usize = 1000;
+
+/// The `Payments` gRPC service, backed by the engine.
+#[derive(Clone)]
+pub struct PaymentsService {
+    payments_engine: PaymentsEngine,
+}
+
+/// Refuses calls without the `[server] api_token` of the config, as the HTTP server does.
+#[derive(Clone)]
+pub struct TokenCheck {
+    config: ServerConfig,
+}
+
+impl Interceptor for TokenCheck {
+    fn call(&mut self, request: Request<()>) -> Result<Request<()>, Status> {
+        let authorization = request
+            .metadata()
+            .get("authorization")
+            .map(|value| value.as_bytes());
+        if self.config.authorized(authorization) {
+            Ok(request)
+        } else {
+            Err(Status::unauthenticated("missing or wrong API token"))
+        }
+    }
+}
+
+/// The service, ready to be served.
+pub fn service(
+    payments_engine: PaymentsEngine,
+) -> InterceptedService<PaymentsServer<PaymentsService>, TokenCheck> {
+    let check = TokenCheck {
+        config: payments_engine.config().server.clone(),
+    };
+    PaymentsServer::with_interceptor(PaymentsService { payments_engine }, check)
+}
+
+/// Serves `service` on `listener` until `shutdown` completes.
+pub async fn serve(
+    listener: TcpListener,
+    service: InterceptedService<PaymentsServer<PaymentsService>, TokenCheck>,
+    shutdown: impl Future<Output = ()> + Send,
+) -> Result<(), tonic::transport::Error> {
+    Server::builder()
+        .add_service(service)
+        .serve_with_incoming_shutdown(TcpIncoming::from(listener), shutdown)
+        .await
+}
+
+/// gRPC status code of a refused row. Parked rows are not refused, they answer `Ok`
+/// with their outcome.
+pub fn code_of(err: &EngineError) -> Code {
+    match err {
+        EngineError::HeldForReview(_) | EngineError::AwaitingTransaction(_) => Code::Ok,
+        EngineError::ClientNotFound | EngineError::TransactionNotFound(_) => Code::NotFound,
+        EngineError::ClientBlocked(_) => Code::PermissionDenied,
+        EngineError::TransactionAlreadyExists => Code::AlreadyExists,
+        EngineError::ClientAccountError(ClientAccountError::NegativeAmount)
+        | EngineError::InvalidDisputeAmount(_)
+        | EngineError::NotClientOwnedTransaction(_, _) => Code::InvalidArgument,
+        EngineError::WriteBuffer => Code::Internal,
+        // Rows valid on their own, refused in the current state of the account
+        _ => Code::FailedPrecondition,
+    }
+}
+
+/// Outcome of a parked row.
+fn parked(err: &EngineError) -> Option<Outcome> {
+    match err {
+        EngineError::HeldForReview(_) => Some(Outcome::HeldForReview),
+        EngineError::AwaitingTransaction(_) => Some(Outcome::AwaitingTransaction),
+        _ => None,
+    }
+}
+
+impl TryFrom<TransactionRequest> for Transaction {
+    type Error = Status;
+
+    fn try_from(request: TransactionRequest) -> Result<Self, Status> {
+        let t_type = match TransactionType::try_from(request.r#type) {
+            Ok(TransactionType::Deposit) => Type::Deposit,
+            Ok(TransactionType::Withdrawal) => Type::Withdrawal,
+            Ok(TransactionType::Dispute) => Type::Dispute,
+            Ok(TransactionType::Resolve) => Type::Resolve,
+            Ok(TransactionType::Chargeback) => Type::Chargeback,
+            Ok(TransactionType::ReverseChargeback) => Type::ReverseChargeback,
+            Ok(TransactionType::CreditLimit) => Type::CreditLimit,
+            Ok(TransactionType::Unspecified) | Err(_) => {
+                return Err(Status::invalid_argument("unknown transaction type"));
+            }
+        };
+        let t_client_id = ClientId::try_from(request.client)
+            .map_err(|_| Status::invalid_argument("client must be up to 65535"))?;
+        let amount = request
+            .amount
+            .map(|amount| {
+                Amount::from_str(&amount)
+                    .map_err(|err| err.to_string())
+                    .and_then(|amount| validate_amount(amount).map_err(str::to_string))
+                    .map_err(Status::invalid_argument)
+            })
+            .transpose()?;
+        Ok(Transaction {
+            t_type,
+            t_client_id,
+            transaction_id: request.tx,
+            amount,
+            timestamp: request.timestamp,
+        })
+    }
+}
+
+fn account(client: ClientId, balances: Balances) -> Account {
+    Account {
+        client: client.into(),
+        available: balances.available.to_string(),
+        held: balances.held.to_string(),
+        total: balances.total.to_string(),
+        locked: balances.locked,
+    }
+}
+
+impl PaymentsService {
+    async fn submit(&self, request: TransactionRequest) -> Result<TransactionReply, Status> {
+        let transaction = Transaction::try_from(request)?;
+        match self.payments_engine.handle_transaction(transaction).await {
+            Ok(()) => Ok(TransactionReply {
+                outcome: Outcome::Applied.into(),
+                message: String::new(),
+            }),
+            Err(err) => match parked(&err) {
+                Some(outcome) => Ok(TransactionReply {
+                    outcome: outcome.into(),
+                    message: err.to_string(),
+                }),
+                None => Err(Status::new(code_of(&err), err.to_string())),
+            },
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Payments for PaymentsService {
+    async fn submit_transaction(
+        &self,
+        request: Request<TransactionRequest>,
+    ) -> Result<Response<TransactionReply>, Status> {
+        self.submit(request.into_inner()).await.map(Response::new)
+    }
+
+    async fn submit_transaction_stream(
+        &self,
+        request: Request<Streaming<TransactionRequest>>,
+    ) -> Result<Response<StreamSummary>, Status> {
+        let mut rows = request.into_inner();
+        let mut summary = StreamSummary::default();
+        while let Some(row) = rows.message().await? {
+            let (client, tx) = (row.client, row.tx);
+            match self.submit(row).await {
+                Ok(reply) if reply.outcome() == Outcome::Applied => summary.applied += 1,
+                Ok(_) => summary.parked += 1,
+                Err(status) => {
+                    summary.refused_count += 1;
+                    if summary.refused.len() < LISTED_REFUSALS {
+                        summary.refused.push(Refusal {
+                            client,
+                            tx,
+                            code: status.code().into(),
+                            message: status.message().to_string(),
+                        });
+                    }
+                }
+            }
+        }
+        Ok(Response::new(summary))
+    }
+
+    async fn get_account(
+        &self,
+        request: Request<GetAccountRequest>,
+    ) -> Result<Response<Account>, Status> {
+        let client = request.into_inner().client;
+        let not_found = || Status::not_found(format!("Client not found: {}", client));
+        let client_id = ClientId::try_from(client).map_err(|_| not_found())?;
+        self.payments_engine
+            .account(client_id)
+            .await
+            .map(|account_state| Response::new(account(client_id, Balances::of(&account_state))))
+            .ok_or_else(not_found)
+    }
+
+    async fn list_accounts(
+        &self,
+        _request: Request<ListAccountsRequest>,
+    ) -> Result<Response<ListAccountsReply>, Status> {
+        let accounts = self
+            .payments_engine
+            .accounts()
+            .await
+            .into_iter()
+            .map(|(client, account_state)| account(client, Balances::of(&account_state)))
+            .collect();
+        Ok(Response::new(ListAccountsReply { accounts }))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::net::SocketAddr;
+
+    use super::*;
+    use crate::config::engine_config::EngineConfig;
+    use proto::payments_client::PaymentsClient;
+
+    async fn start(payments_engine: PaymentsEngine) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(serve(
+            listener,
+            service(payments_engine),
+            std::future::pending(),
+        ));
+        address
+    }
+
+    async fn connect(address: SocketAddr) -> PaymentsClient<tonic::transport::Channel> {
+        PaymentsClient::connect(format!("http://{}", address))
+            .await
+            .unwrap()
+    }
+
+    fn row(
+        t_type: TransactionType,
+        client: u32,
+        tx: u32,
+        amount: Option<&str>,
+    ) -> TransactionRequest {
+        TransactionRequest {
+            r#type: t_type.into(),
+            client,
+            tx,
+            amount: amount.map(str::to_string),
+            timestamp: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn submit_and_read_accounts() {
+        let mut client = connect(start(PaymentsEngine::new()).await).await;
+        let reply = client
+            .submit_transaction(row(TransactionType::Deposit, 1, 1, Some("2.5")))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(reply.outcome(), Outcome::Applied);
+
+        let refused = [
+            (
+                row(TransactionType::Deposit, 1, 1, Some("1")),
+                Code::AlreadyExists,
+            ),
+            (
+                row(TransactionType::Withdrawal, 1, 2, Some("10")),
+                Code::FailedPrecondition,
+            ),
+            (row(TransactionType::Dispute, 1, 9, None), Code::NotFound),
+            (
+                row(TransactionType::Deposit, 1, 3, Some("-1")),
+                Code::InvalidArgument,
+            ),
+            (
+                row(TransactionType::Deposit, 70_000, 4, Some("1")),
+                Code::InvalidArgument,
+            ),
+            (
+                row(TransactionType::Unspecified, 1, 5, None),
+                Code::InvalidArgument,
+            ),
+        ];
+        for (request, code) in refused {
+            assert_eq!(
+                client.submit_transaction(request).await.unwrap_err().code(),
+                code
+            );
+        }
+
+        let account = client
+            .get_account(GetAccountRequest { client: 1 })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(
+            account,
+            Account {
+                client: 1,
+                available: "2.5".to_string(),
+                held: "0".to_string(),
+                total: "2.5".to_string(),
+                locked: false,
+            }
+        );
+        let status = client
+            .get_account(GetAccountRequest { client: 2 })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::NotFound);
+        let accounts = client
+            .list_accounts(ListAccountsRequest {})
+            .await
+            .unwrap()
+            .into_inner()
+            .accounts;
+        assert_eq!(accounts, vec![account]);
+    }
+
+    #[tokio::test]
+    async fn concurrent_streams() {
+        let address = start(PaymentsEngine::new()).await;
+        let mut streams = tokio::task::JoinSet::new();
+        for client in 1..=8 {
+            streams.spawn(async move {
+                let mut rows: Vec<TransactionRequest> = (0..100)
+                    .map(|i| {
+                        row(
+                            TransactionType::Deposit,
+                            client,
+                            client * 1000 + i,
+                            Some("1"),
+                        )
+                    })
+                    .collect();
+                // Refused, the stream goes on
+                rows.insert(
+                    50,
+                    row(
+                        TransactionType::Withdrawal,
+                        client,
+                        client * 1000 + 500,
+                        Some("1000"),
+                    ),
+                );
+                connect(address)
+                    .await
+                    .submit_transaction_stream(futures_util::stream::iter(rows))
+                    .await
+                    .unwrap()
+                    .into_inner()
+            });
+        }
+        for summary in streams.join_all().await {
+            assert_eq!(summary.applied, 100);
+            assert_eq!(summary.parked, 0);
+            assert_eq!(summary.refused.len(), 1);
+            assert_eq!(summary.refused_count, 1);
+            assert_eq!(summary.refused[0].code, i32::from(Code::FailedPrecondition));
+        }
+
+        let accounts = connect(address)
+            .await
+            .list_accounts(ListAccountsRequest {})
+            .await
+            .unwrap()
+            .into_inner()
+            .accounts;
+        assert_eq!(accounts.len(), 8);
+        assert!(accounts.iter().all(|account| account.total == "100"));
+    }
+
+    #[tokio::test]
+    async fn api_token() {
+        let config = EngineConfig::from_toml("[server]\napi_token = \"s3cret\"").unwrap();
+        let address = start(PaymentsEngine::with_config(config)).await;
+        let mut client = connect(address).await;
+        let status = client
+            .list_accounts(ListAccountsRequest {})
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+
+        let mut request = Request::new(ListAccountsRequest {});
+        request
+            .metadata_mut()
+            .insert("authorization", "Bearer s3cret".parse().unwrap());
+        assert!(client.list_accounts(request).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn refusals_are_capped() {
+        let address = start(PaymentsEngine::new()).await;
+        let refused = LISTED_REFUSALS as u32 + 5;
+        let rows: Vec<TransactionRequest> = (1..=refused)
+            .map(|tx| row(TransactionType::Dispute, 1, tx, None))
+            .collect();
+        let summary = connect(address)
+            .await
+            .submit_transaction_stream(futures_util::stream::iter(rows))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(summary.refused_count, u64::from(refused));
+        assert_eq!(summary.refused.len(), LISTED_REFUSALS);
+        assert_eq!(summary.refused[0].tx, 1);
+    }
+
+    #[test]
+    fn error_codes() {
+        assert_eq!(code_of(&EngineError::HeldForReview(1)), Code::Ok);
+        assert_eq!(
+            code_of(&EngineError::ClientBlocked(1)),
+            Code::PermissionDenied
+        );
+        assert_eq!(
+            code_of(&EngineError::TransactionAlreadyDisputed(1)),
+            Code::FailedPrecondition
+        );
+        assert_eq!(
+            code_of(&EngineError::ClientAccountError(ClientAccountError::Locked)),
+            Code::FailedPrecondition
+        );
+        assert_eq!(
+            code_of(&EngineError::NotClientOwnedTransaction(1, 2)),
+            Code::InvalidArgument
+        );
+        assert_eq!(code_of(&EngineError::WriteBuffer), Code::Internal);
+    }
+}
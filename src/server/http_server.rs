@@ -0,0 +1,413 @@
+use std::future::Future;
+use std::sync::Arc;
+
+use axum::extract::ws::WebSocketUpgrade;
+use axum::extract::{Path, Request, State};
+use axum::http::{StatusCode, header};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Serialize;
+use tokio::net::TcpListener;
+
+use crate::engine::error::EngineError;
+use crate::engine::payments_engine::PaymentsEngine;
+use crate::history::account_history::Balances;
+use crate::observe::channel_sink::ChannelSink;
+use crate::server::balance_socket::balance_socket;
+use crate::transaction::Transaction;
+use crate::types::ClientId;
+
+/// Changes a WebSocket may fall behind by before it misses some.
+pub const CHANGES_CAPACITY: usize = 1024;
+
+#[derive(Clone)]
+struct ServerState {
+    payments_engine: PaymentsEngine,
+    changes: ChannelSink,
+}
+
+/// Balances of one client, as in the CSV output.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AccountView {
+    pub client: ClientId,
+    #[serde(flatten)]
+    pub balances: Balances,
+}
+
+/// What the engine made of a submitted row: `applied` or the error.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TransactionOutcome {
+    pub outcome: String,
+}
+
+/// Routes of the server mode:
+///
+/// - `POST /transactions`: applies one row given as JSON, with the CSV column names.
+/// - `GET /accounts` and `GET /accounts/{client}`: current balances.
+/// - `GET /ws`: WebSocket of balance updates, see `balance_socket`.
+///
+/// With a `[server] api_token` in the config, every route requires it.
+pub async fn router(payments_engine: PaymentsEngine) -> Router {
+    let changes = ChannelSink::new(CHANGES_CAPACITY);
+    payments_engine.subscribe(Arc::new(changes.clone())).await;
+    let state = ServerState {
+        payments_engine,
+        changes,
+    };
+    Router::new()
+        .route("/transactions", post(submit_transaction))
+        .route("/accounts", get(list_accounts))
+        .route("/accounts/{client}", get(get_account))
+        .route("/ws", get(balance_updates))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_token))
+        .with_state(state)
+}
+
+async fn require_token(State(state): State<ServerState>, request: Request, next: Next) -> Response {
+    let authorization = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .map(|value| value.as_bytes());
+    if state
+        .payments_engine
+        .config()
+        .server
+        .authorized(authorization)
+    {
+        next.run(request).await
+    } else {
+        StatusCode::UNAUTHORIZED.into_response()
+    }
+}
+
+/// Serves `router` on `listener` until `shutdown` completes.
+pub async fn serve(
+    listener: TcpListener,
+    router: Router,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> std::io::Result<()> {
+    axum::serve(listener, router)
+        .with_graceful_shutdown(shutdown)
+        .await
+}
+
+/// HTTP status of a refused row. Parked rows are accepted: they may still be applied.
+pub fn status_of(err: &EngineError) -> StatusCode {
+    match err {
+        EngineError::HeldForReview(_) | EngineError::AwaitingTransaction(_) => StatusCode::ACCEPTED,
+        EngineError::ClientNotFound | EngineError::TransactionNotFound(_) => StatusCode::NOT_FOUND,
+        EngineError::ClientBlocked(_) => StatusCode::FORBIDDEN,
+        EngineError::TransactionAlreadyExists
+        | EngineError::TransactionAlreadyDisputed(_)
+        | EngineError::TransactionNotDisputed(_)
+        | EngineError::TransactionAlreadyResolved(_)
+        | EngineError::TransactionAlreadyChargedBack(_)
+        | EngineError::TransactionNotChargedBack(_)
+        | EngineError::TransactionReversed(_) => StatusCode::CONFLICT,
+        EngineError::WriteBuffer => StatusCode::INTERNAL_SERVER_ERROR,
+        _ => StatusCode::UNPROCESSABLE_ENTITY,
+    }
+}
+
+async fn submit_transaction(
+    State(state): State<ServerState>,
+    Json(transaction): Json<Transaction>,
+) -> (StatusCode, Json<TransactionOutcome>) {
+    match state.payments_engine.handle_transaction(transaction).await {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(TransactionOutcome {
+                outcome: "applied".to_string(),
+            }),
+        ),
+        Err(err) => (
+            status_of(&err),
+            Json(TransactionOutcome {
+                outcome: err.to_string(),
+            }),
+        ),
+    }
+}
+
+async fn list_accounts(State(state): State<ServerState>) -> Json<Vec<AccountView>> {
+    Json(
+        state
+            .payments_engine
+            .accounts()
+            .await
+            .into_iter()
+            .map(|(client, account)| AccountView {
+                client,
+                balances: Balances::of(&account),
+            })
+            .collect(),
+    )
+}
+
+async fn get_account(
+    State(state): State<ServerState>,
+    Path(client): Path<ClientId>,
+) -> Result<Json<AccountView>, StatusCode> {
+    state
+        .payments_engine
+        .account(client)
+        .await
+        .map(|account| {
+            Json(AccountView {
+                client,
+                balances: Balances::of(&account),
+            })
+        })
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn balance_updates(State(state): State<ServerState>, upgrade: WebSocketUpgrade) -> Response {
+    // Subscribed before the upgrade completes, so no change in between is missed
+    let changes = state.changes.subscribe();
+    upgrade.on_upgrade(move |socket| balance_socket(socket, state.payments_engine, changes))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use axum::body::Body;
+    use axum::http::Request;
+    use futures_util::{SinkExt, StreamExt};
+    use rust_decimal::dec;
+    use tokio_tungstenite::tungstenite;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::config::engine_config::EngineConfig;
+    use crate::transaction::Type;
+
+    async fn post_transaction(router: &Router, body: &str) -> (StatusCode, String) {
+        let response = router
+            .clone()
+            .oneshot(
+                Request::post("/transactions")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn submit_and_read_accounts() {
+        let router = router(PaymentsEngine::new()).await;
+        let (status, body) = post_transaction(
+            &router,
+            r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "2.5"}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, r#"{"outcome":"applied"}"#);
+
+        let (status, _) = post_transaction(
+            &router,
+            r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "1"}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        let (status, _) = post_transaction(
+            &router,
+            r#"{"type": "withdrawal", "client": 1, "tx": 2, "amount": "10"}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let response = router
+            .clone()
+            .oneshot(Request::get("/accounts/1").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(
+            &body[..],
+            br#"{"client":1,"available":"2.5","held":"0","total":"2.5","locked":false}"#
+        );
+        let response = router
+            .oneshot(Request::get("/accounts/2").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn api_token() {
+        let config = EngineConfig::from_toml("[server]\napi_token = \"s3cret\"").unwrap();
+        let router = router(PaymentsEngine::with_config(config)).await;
+        let get_accounts = |authorization: Option<&str>| {
+            let mut request = Request::get("/accounts");
+            if let Some(authorization) = authorization {
+                request = request.header("authorization", authorization);
+            }
+            router.clone().oneshot(request.body(Body::empty()).unwrap())
+        };
+        assert_eq!(
+            get_accounts(None).await.unwrap().status(),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            get_accounts(Some("Bearer wrong")).await.unwrap().status(),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            get_accounts(Some("Bearer s3cret")).await.unwrap().status(),
+            StatusCode::OK
+        );
+        let (status, _) = post_transaction(
+            &router,
+            r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "2.5"}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    async fn next_message(
+        socket: &mut tokio_tungstenite::WebSocketStream<
+            tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+        >,
+    ) -> serde_json::Value {
+        loop {
+            match socket.next().await.unwrap().unwrap() {
+                tungstenite::Message::Text(text) => return serde_json::from_str(&text).unwrap(),
+                _ => continue,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn balance_updates_over_websocket() {
+        let payments_engine = PaymentsEngine::new();
+        let deposit = |client, tx| Transaction {
+            t_type: Type::Deposit,
+            t_client_id: client,
+            transaction_id: tx,
+            amount: Some(dec!(10)),
+            timestamp: None,
+        };
+        payments_engine
+            .handle_transaction(deposit(1, 1))
+            .await
+            .unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let router = router(payments_engine.clone()).await;
+        let server = tokio::spawn(serve(listener, router, std::future::pending()));
+
+        let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws", address))
+            .await
+            .unwrap();
+        socket
+            .send(tungstenite::Message::Text(r#"{"subscribe": [1]}"#.into()))
+            .await
+            .unwrap();
+        let balance = next_message(&mut socket).await;
+        assert_eq!(balance["type"], "balance");
+        assert_eq!(balance["available"], "10");
+
+        // Client 2 is not followed
+        payments_engine
+            .handle_transaction(deposit(2, 2))
+            .await
+            .unwrap();
+        for (t_type, tx) in [(Type::Dispute, 1), (Type::Chargeback, 1)] {
+            payments_engine
+                .handle_transaction(Transaction {
+                    t_type,
+                    t_client_id: 1,
+                    transaction_id: tx,
+                    amount: None,
+                    timestamp: None,
+                })
+                .await
+                .unwrap();
+        }
+        let dispute = next_message(&mut socket).await;
+        assert_eq!(dispute["type"], "delta");
+        assert_eq!(dispute["cause"], "dispute");
+        assert_eq!(dispute["held_change"], "10");
+        let chargeback = next_message(&mut socket).await;
+        assert_eq!(chargeback["cause"], "chargeback");
+        let lock = next_message(&mut socket).await;
+        assert_eq!(lock["type"], "lock");
+        assert_eq!(lock["locked"], true);
+
+        socket
+            .send(tungstenite::Message::Text("{}".into()))
+            .await
+            .unwrap();
+        assert_eq!(next_message(&mut socket).await["type"], "error");
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn lagging_socket_gets_balances() {
+        let payments_engine = PaymentsEngine::new();
+        let changes = ChannelSink::new(2);
+        payments_engine.subscribe(Arc::new(changes.clone())).await;
+        let receiver = Arc::new(std::sync::Mutex::new(Some(changes.subscribe())));
+        // More changes than the channel keeps, before the socket reads any
+        for tx in 1..=5 {
+            payments_engine
+                .handle_transaction(Transaction {
+                    t_type: Type::Deposit,
+                    t_client_id: 1,
+                    transaction_id: tx,
+                    amount: Some(dec!(1)),
+                    timestamp: None,
+                })
+                .await
+                .unwrap();
+        }
+
+        let engine = payments_engine.clone();
+        let router = Router::new().route(
+            "/ws",
+            get(move |upgrade: WebSocketUpgrade| async move {
+                let changes = receiver.lock().unwrap().take().unwrap();
+                upgrade.on_upgrade(move |socket| balance_socket(socket, engine, changes))
+            }),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = tokio::spawn(serve(listener, router, std::future::pending()));
+
+        let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws", address))
+            .await
+            .unwrap();
+        socket
+            .send(tungstenite::Message::Text(r#"{"subscribe": "all"}"#.into()))
+            .await
+            .unwrap();
+        // The missed changes may be noticed before or after the subscription, either
+        // way the current balances follow
+        let mut missed = None;
+        loop {
+            let message = next_message(&mut socket).await;
+            match message["type"].as_str().unwrap() {
+                "lagged" => missed = message["missed"].as_u64(),
+                "balance" if missed.is_some() => {
+                    assert_eq!(message["total"], "5");
+                    break;
+                }
+                "balance" => {}
+                other => panic!("unexpected {}", other),
+            }
+        }
+        assert_eq!(missed, Some(3));
+        server.abort();
+    }
+}
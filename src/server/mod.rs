@@ -0,0 +1,7 @@
+//! Server modes: rows submitted over HTTP or gRPC, balances read back, and live balance
+//! updates over a WebSocket.
+
+pub mod balance_socket;
+#[cfg(feature = "grpc")]
+pub mod grpc_server;
+pub mod http_server;
@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::client::client_account::ClientAccount;
+use crate::fees::fee_ledger::FeeLedger;
+use crate::history::account_history::AccountHistory;
+use crate::rules::rules_engine::RulesEngine;
+use crate::seen_index::SeenIndex;
+use crate::snapshot::error::SnapshotError;
+use crate::snapshot::input_checkpoint::InputCheckpoint;
+use crate::storage::{PendingBuffer, ReviewQueue, TransactionsDatabase};
+use crate::types::{ClientId, EventPosition};
+
+/// Everything a `PaymentsEngine` needs to continue in a later run. The configuration is
+/// not part of it and is passed again when the engine is restored.
+/// Sections missing from older state files start out empty.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EngineSnapshot {
+    pub clients: HashMap<ClientId, ClientAccount>,
+    pub transactions_database: TransactionsDatabase,
+    pub position: EventPosition,
+    pub fee_ledger: FeeLedger,
+    pub rules: RulesEngine,
+    pub review_queue: ReviewQueue,
+    pub pending_buffer: PendingBuffer,
+    pub seen_index: SeenIndex,
+    pub history: AccountHistory,
+    /// Where the input was read up to when the snapshot was taken.
+    pub input_checkpoint: Option<InputCheckpoint>,
+}
+
+impl EngineSnapshot {
+    /// Loads a snapshot, or `None` if the file does not exist yet.
+    pub fn load(path: impl AsRef<Path>) -> Result<Option<Self>, SnapshotError> {
+        match std::fs::File::open(path) {
+            Ok(file) => Ok(Some(serde_json::from_reader(std::io::BufReader::new(
+                file,
+            ))?)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Writes the snapshot next to `path` first and renames it over, so a crash never
+    /// leaves a half written state file behind. Returns the size of the file written.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<u64, SnapshotError> {
+        let path = path.as_ref();
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(&temporary)?);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+        let size = writer.get_ref().metadata()?.len();
+        drop(writer);
+
+        std::fs::rename(&temporary, path)?;
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use rust_decimal::dec;
+
+    use super::*;
+
+    #[test]
+    fn save_and_load() {
+        let path =
+            std::env::temp_dir().join(format!("engine_snapshot_{}.json", std::process::id()));
+
+        let mut snapshot = EngineSnapshot::default();
+        let mut client = ClientAccount::new();
+        client.deposit(dec!(1.5000)).unwrap();
+        snapshot.clients.insert(1, client);
+        snapshot.position.sequence = 7;
+
+        assert!(snapshot.save(&path).unwrap() > 0);
+        let loaded = EngineSnapshot::load(&path).unwrap().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.clients.get(&1).unwrap().available(), dec!(1.5000));
+        assert_eq!(loaded.position.sequence, 7);
+    }
+
+    #[test]
+    fn load_missing() {
+        let path = std::env::temp_dir().join("engine_snapshot_missing.json");
+
+        assert!(EngineSnapshot::load(path).unwrap().is_none());
+    }
+}
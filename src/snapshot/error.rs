@@ -0,0 +1,10 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum SnapshotError {
+    #[error("Error accessing state file: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid state file: {0}")]
+    Json(#[from] serde_json::Error),
+}
@@ -0,0 +1,107 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/// Bytes at the start of an input file that identify it.
+const FINGERPRINT_BYTES: u64 = 4096;
+
+/// How far an input file was processed, saved together with the engine state so an
+/// interrupted run can continue right after the last row the state includes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputCheckpoint {
+    /// Canonical path of the input file.
+    pub path: PathBuf,
+    /// Hash of the first bytes of the file, up to the checkpoint. Appending rows keeps
+    /// it, replacing the file with another one does not.
+    pub fingerprint: u64,
+    /// Offset of the next row to read.
+    pub byte: u64,
+    pub line: u64,
+    /// Number of records read so far, header included.
+    pub record: u64,
+}
+
+impl InputCheckpoint {
+    /// Checkpoint of the file at `path`, read up to `position`.
+    pub fn take(path: impl AsRef<Path>, position: &csv::Position) -> std::io::Result<Self> {
+        let path = std::fs::canonicalize(path)?;
+        Ok(Self {
+            fingerprint: fingerprint(&path, position.byte())?,
+            path,
+            byte: position.byte(),
+            line: position.line(),
+            record: position.record(),
+        })
+    }
+
+    /// Whether this checkpoint was taken on the file at `path`, possibly since grown.
+    pub fn is_for(&self, path: impl AsRef<Path>) -> std::io::Result<bool> {
+        let path = std::fs::canonicalize(path)?;
+        Ok(self.path == path && self.fingerprint == fingerprint(&path, self.byte)?)
+    }
+
+    pub fn position(&self) -> csv::Position {
+        let mut position = csv::Position::new();
+        position
+            .set_byte(self.byte)
+            .set_line(self.line)
+            .set_record(self.record);
+        position
+    }
+}
+
+fn fingerprint(path: &Path, byte: u64) -> std::io::Result<u64> {
+    let mut head = Vec::new();
+    std::fs::File::open(path)?
+        .take(byte.min(FINGERPRINT_BYTES))
+        .read_to_end(&mut head)?;
+    Ok(fnv1a(&head))
+}
+
+/// 64 bit FNV-1a, stable across builds unlike the standard library hashers.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn resume_from_checkpoint() {
+        let path =
+            std::env::temp_dir().join(format!("input_checkpoint_{}.csv", std::process::id()));
+        std::fs::write(
+            &path,
+            "type,client,tx,amount\ndeposit,1,1,1.0\ndeposit,1,2,2.0\n",
+        )
+        .unwrap();
+
+        let mut reader = csv::Reader::from_path(&path).unwrap();
+        let mut record = csv::StringRecord::new();
+        assert!(reader.read_record(&mut record).unwrap());
+        let checkpoint = InputCheckpoint::take(&path, reader.position()).unwrap();
+        assert_eq!(checkpoint.record, 2);
+
+        // Rows appended after the checkpoint keep the file identity
+        std::fs::write(
+            &path,
+            "type,client,tx,amount\ndeposit,1,1,1.0\ndeposit,1,2,2.0\ndeposit,1,3,3.0\n",
+        )
+        .unwrap();
+        assert!(checkpoint.is_for(&path).unwrap());
+
+        let mut reader = csv::Reader::from_path(&path).unwrap();
+        reader.seek(checkpoint.position()).unwrap();
+        let rest: Vec<csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+        assert_eq!(rest.len(), 2);
+        assert_eq!(&rest[0][2], "2");
+
+        std::fs::write(&path, "type,client,tx,amount\ndeposit,2,1,1.0\n").unwrap();
+        assert!(!checkpoint.is_for(&path).unwrap());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
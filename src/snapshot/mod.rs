@@ -0,0 +1,3 @@
+pub mod engine_snapshot;
+pub mod error;
+pub mod input_checkpoint;
@@ -1,10 +1,92 @@
-use crate::types::{Amount, ClientId, TransactionId};
-use std::collections::HashMap;
+use crate::engine::error::EngineError;
+use crate::engine::transaction_state::{DisputeAction, TransactionState};
+use crate::transaction::Transaction;
+use crate::types::{Amount, ClientId, EventPosition, TransactionId};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 
-pub type TransactionType = (ClientId, Amount);
+/// A stored deposit and what happened to each portion of it. A portion is disputed,
+/// then resolved or charged back, and a charged back portion may be reversed.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StoredTransaction {
+    pub client_id: ClientId,
+    pub amount: Amount,
+    pub disputed: Amount,
+    #[serde(default)]
+    pub resolved: Amount,
+    pub charged_back: Amount,
+    #[serde(default)]
+    pub reversed: Amount,
+    /// When the deposit was applied.
+    pub recorded: EventPosition,
+    /// When the currently open dispute was raised.
+    pub disputed_at: Option<EventPosition>,
+}
+
+impl StoredTransaction {
+    pub fn new(client_id: ClientId, amount: Amount, recorded: EventPosition) -> Self {
+        Self {
+            client_id,
+            amount,
+            disputed: Decimal::ZERO,
+            resolved: Decimal::ZERO,
+            charged_back: Decimal::ZERO,
+            reversed: Decimal::ZERO,
+            recorded,
+            disputed_at: None,
+        }
+    }
+
+    /// Part of the original amount that was never disputed.
+    pub fn undisputed(&self) -> Amount {
+        self.amount - self.disputed - self.resolved - self.charged_back - self.reversed
+    }
+
+    pub fn state(&self) -> TransactionState {
+        if !self.disputed.is_zero() {
+            TransactionState::Disputed
+        } else if !self.charged_back.is_zero() {
+            TransactionState::ChargedBack
+        } else if !self.reversed.is_zero() {
+            TransactionState::Reversed
+        } else if !self.resolved.is_zero() {
+            TransactionState::Resolved
+        } else {
+            TransactionState::Settled
+        }
+    }
 
+    /// The portion `action` applies to: the undisputed value for a dispute, the disputed
+    /// one for a resolve or chargeback, the charged back one for a reversal. Refused when
+    /// it is empty.
+    pub fn portion_for(
+        &self,
+        action: DisputeAction,
+        transaction_id: TransactionId,
+    ) -> Result<Amount, EngineError> {
+        let portion = match action {
+            DisputeAction::Dispute => self.undisputed(),
+            DisputeAction::Resolve | DisputeAction::Chargeback => self.disputed,
+            DisputeAction::ReverseChargeback => self.charged_back,
+        };
+        if portion.is_zero() {
+            Err(self.state().refusal(action, transaction_id))
+        } else {
+            Ok(portion)
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct TransactionsDatabase {
-    transactions: HashMap<TransactionId, TransactionType>,
+    transactions: HashMap<TransactionId, StoredTransaction>,
+}
+
+impl Default for TransactionsDatabase {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TransactionsDatabase {
@@ -14,24 +96,176 @@ impl TransactionsDatabase {
         }
     }
 
-    pub fn insert(&mut self, transaction_id: TransactionId, transaction: TransactionType) {
+    pub fn insert(&mut self, transaction_id: TransactionId, transaction: StoredTransaction) {
         self.transactions.insert(transaction_id, transaction);
     }
 
-    pub fn get(&self, transaction_id: TransactionId) -> Option<TransactionType> {
+    pub fn get(&self, transaction_id: TransactionId) -> Option<StoredTransaction> {
         self.transactions.get(&transaction_id).copied()
     }
 
+    pub fn get_mut(&mut self, transaction_id: TransactionId) -> Option<&mut StoredTransaction> {
+        self.transactions.get_mut(&transaction_id)
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&TransactionId, &mut StoredTransaction)> {
+        self.transactions.iter_mut()
+    }
+
     pub fn contains_key(&self, transaction_id: TransactionId) -> bool {
         self.transactions.contains_key(&transaction_id)
     }
 }
 
+/// Rows parked by a `hold` risk rule until they are approved or rejected, in arrival order.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ReviewQueue {
+    transactions: Vec<Transaction>,
+}
+
+impl ReviewQueue {
+    pub fn new() -> Self {
+        Self {
+            transactions: Vec::new(),
+        }
+    }
+
+    pub fn park(&mut self, transaction: Transaction) {
+        self.transactions.push(transaction);
+    }
+
+    /// Removes the oldest parked row with `transaction_id`.
+    pub fn take(&mut self, transaction_id: TransactionId) -> Option<Transaction> {
+        let index = self
+            .transactions
+            .iter()
+            .position(|transaction| transaction.transaction_id == transaction_id)?;
+        Some(self.transactions.remove(index))
+    }
+
+    pub fn transactions(&self) -> &[Transaction] {
+        &self.transactions
+    }
+}
+
+/// A dispute, resolve or chargeback row waiting for the transaction it refers to.
+#[derive(Clone, Serialize, Deserialize)]
+struct PendingRow {
+    transaction: Transaction,
+    parked: EventPosition,
+}
+
+/// Serialized form of the pending buffer: its rows in arrival order.
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct PendingRows {
+    rows: Vec<PendingRow>,
+}
+
+/// Rows that arrived before their target transaction, in arrival order, indexed by the
+/// transaction they wait for.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(from = "PendingRows", into = "PendingRows")]
+pub struct PendingBuffer {
+    rows: BTreeMap<u64, PendingRow>,
+    waiting: HashMap<TransactionId, Vec<u64>>,
+    next_arrival: u64,
+}
+
+impl From<PendingRows> for PendingBuffer {
+    fn from(pending_rows: PendingRows) -> Self {
+        let mut pending_buffer = Self::new();
+        for row in pending_rows.rows {
+            pending_buffer.park(row.transaction, row.parked);
+        }
+        pending_buffer
+    }
+}
+
+impl From<PendingBuffer> for PendingRows {
+    fn from(pending_buffer: PendingBuffer) -> Self {
+        Self {
+            rows: pending_buffer.rows.into_values().collect(),
+        }
+    }
+}
+
+impl PendingBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn park(&mut self, transaction: Transaction, parked: EventPosition) {
+        let arrival = self.next_arrival;
+        self.next_arrival += 1;
+        self.waiting
+            .entry(transaction.transaction_id)
+            .or_default()
+            .push(arrival);
+        self.rows.insert(
+            arrival,
+            PendingRow {
+                transaction,
+                parked,
+            },
+        );
+    }
+
+    /// Removes every row waiting for `transaction_id`, oldest first.
+    pub fn take(&mut self, transaction_id: TransactionId) -> Vec<Transaction> {
+        self.waiting
+            .remove(&transaction_id)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|arrival| self.rows.remove(&arrival))
+            .map(|row| row.transaction)
+            .collect()
+    }
+
+    /// Removes the oldest rows for which `expired` holds at the position they were
+    /// parked at, oldest first.
+    pub fn expire(&mut self, expired: impl Fn(EventPosition) -> bool) -> Vec<Transaction> {
+        let mut taken = Vec::new();
+        while let Some(entry) = self.rows.first_entry()
+            && expired(entry.get().parked)
+        {
+            let row = entry.remove();
+            self.unindex(row.transaction.transaction_id);
+            taken.push(row.transaction);
+        }
+        taken
+    }
+
+    /// Removes every row, oldest first.
+    pub fn drain(&mut self) -> Vec<Transaction> {
+        self.waiting.clear();
+        std::mem::take(&mut self.rows)
+            .into_values()
+            .map(|row| row.transaction)
+            .collect()
+    }
+
+    pub fn transactions(&self) -> impl Iterator<Item = &Transaction> {
+        self.rows.values().map(|row| &row.transaction)
+    }
+
+    /// Drops the oldest arrival waiting for `transaction_id`, the one just expired.
+    fn unindex(&mut self, transaction_id: TransactionId) {
+        if let Some(arrivals) = self.waiting.get_mut(&transaction_id) {
+            arrivals.remove(0);
+            if arrivals.is_empty() {
+                self.waiting.remove(&transaction_id);
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
     use rust_decimal::dec;
 
     use super::*;
+    use crate::config::engine_config::Window;
+    use crate::transaction::Type;
 
     //TransactionsDatabase
     #[test]
@@ -42,7 +276,7 @@ pub mod tests {
 
         let mut transactions_database = TransactionsDatabase::new();
 
-        let transaction: TransactionType = (t_client_id, amount);
+        let transaction = StoredTransaction::new(t_client_id, amount, EventPosition::default());
 
         transactions_database.insert(transaction_id, transaction);
 
@@ -60,7 +294,7 @@ pub mod tests {
 
         let mut transactions_database = TransactionsDatabase::new();
 
-        let transaction: TransactionType = (t_client_id, amount);
+        let transaction = StoredTransaction::new(t_client_id, amount, EventPosition::default());
 
         transactions_database.insert(transaction_id, transaction);
 
@@ -68,4 +302,127 @@ pub mod tests {
 
         assert!(received_amout.is_none());
     }
+
+    #[test]
+    fn review_queue() {
+        let mut review_queue = ReviewQueue::new();
+        let transaction = |t_type, transaction_id| Transaction {
+            t_type,
+            t_client_id: 1,
+            transaction_id,
+            amount: Some(dec!(1.000)),
+            timestamp: None,
+        };
+
+        review_queue.park(transaction(Type::Deposit, 1));
+        review_queue.park(transaction(Type::Withdrawal, 2));
+        assert_eq!(review_queue.transactions().len(), 2);
+
+        assert_eq!(review_queue.take(2), Some(transaction(Type::Withdrawal, 2)));
+        assert_eq!(review_queue.take(2), None);
+        assert_eq!(
+            review_queue.transactions(),
+            &[transaction(Type::Deposit, 1)]
+        );
+    }
+
+    #[test]
+    fn pending_buffer() {
+        let mut pending_buffer = PendingBuffer::new();
+        let transaction = |t_type, transaction_id| Transaction {
+            t_type,
+            t_client_id: 1,
+            transaction_id,
+            amount: None,
+            timestamp: None,
+        };
+        let position = |sequence| EventPosition {
+            sequence,
+            timestamp: None,
+        };
+
+        pending_buffer.park(transaction(Type::Dispute, 1), position(1));
+        pending_buffer.park(transaction(Type::Dispute, 2), position(2));
+        pending_buffer.park(transaction(Type::Resolve, 1), position(3));
+
+        assert_eq!(
+            pending_buffer.take(1),
+            vec![transaction(Type::Dispute, 1), transaction(Type::Resolve, 1)]
+        );
+        assert!(pending_buffer.take(1).is_empty());
+
+        let expired_at = |now| move |parked| Window::Events(2).exceeded(parked, position(now));
+        assert!(pending_buffer.expire(expired_at(4)).is_empty());
+        assert_eq!(
+            pending_buffer.expire(expired_at(5)),
+            vec![transaction(Type::Dispute, 2)]
+        );
+        assert_eq!(pending_buffer.transactions().count(), 0);
+        assert!(pending_buffer.waiting.is_empty());
+
+        // Saved as the rows in arrival order, as before the index
+        pending_buffer.park(transaction(Type::Chargeback, 3), position(6));
+        pending_buffer.park(transaction(Type::Dispute, 3), position(7));
+        let json = serde_json::to_string(&pending_buffer).unwrap();
+        assert!(json.starts_with(r#"{"rows":[{"transaction":{"type":"chargeback""#));
+        let mut restored: PendingBuffer = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            restored.take(3),
+            vec![
+                transaction(Type::Chargeback, 3),
+                transaction(Type::Dispute, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn stored_transaction_undisputed() {
+        let mut transaction = StoredTransaction::new(1, dec!(10.0000), EventPosition::default());
+        assert_eq!(transaction.undisputed(), dec!(10.0000));
+
+        transaction.disputed = dec!(4.0000);
+        transaction.charged_back = dec!(1.5000);
+        transaction.resolved = dec!(1.0000);
+        transaction.reversed = dec!(0.5000);
+        assert_eq!(transaction.undisputed(), dec!(3.0000));
+    }
+
+    #[test]
+    fn stored_transaction_portions() {
+        let mut transaction = StoredTransaction::new(1, dec!(10.0000), EventPosition::default());
+        assert_eq!(transaction.state(), TransactionState::Settled);
+        assert_eq!(
+            transaction.portion_for(DisputeAction::Resolve, 1),
+            Err(EngineError::TransactionNotDisputed(1))
+        );
+
+        // Part resolved, the rest can still be disputed
+        transaction.resolved = dec!(4.0000);
+        assert_eq!(transaction.state(), TransactionState::Resolved);
+        assert_eq!(
+            transaction.portion_for(DisputeAction::Dispute, 1),
+            Ok(dec!(6.0000))
+        );
+
+        // Part charged back while the rest is disputed: both can move on
+        transaction.resolved = dec!(0.0000);
+        transaction.disputed = dec!(4.0000);
+        transaction.charged_back = dec!(6.0000);
+        assert_eq!(transaction.state(), TransactionState::Disputed);
+        assert_eq!(
+            transaction.portion_for(DisputeAction::ReverseChargeback, 1),
+            Ok(dec!(6.0000))
+        );
+        assert_eq!(
+            transaction.portion_for(DisputeAction::Dispute, 1),
+            Err(EngineError::TransactionAlreadyDisputed(1))
+        );
+
+        transaction.disputed = dec!(0.0000);
+        assert_eq!(transaction.state(), TransactionState::ChargedBack);
+        assert_eq!(
+            transaction.portion_for(DisputeAction::Chargeback, 1),
+            Err(EngineError::TransactionAlreadyChargedBack(1))
+        );
+    }
 }
@@ -1,7 +1,7 @@
-use crate::types::{Amount, ClientId, TransactionId};
-use serde::{Deserialize, Deserializer};
+use crate::types::{Amount, ClientId, Timestamp, TransactionId};
+use serde::{Deserialize, Deserializer, Serialize};
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
 pub enum Type {
     #[serde(rename = "deposit")]
     Deposit,
@@ -13,9 +13,36 @@ pub enum Type {
     Resolve,
     #[serde(rename = "chargeback")]
     Chargeback,
+    #[serde(rename = "reverse_chargeback")]
+    ReverseChargeback,
+    /// Admin row setting the client's credit limit to `amount`.
+    #[serde(rename = "credit_limit")]
+    CreditLimit,
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+impl Type {
+    /// Name of the type in the input files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Type::Deposit => "deposit",
+            Type::Withdrawal => "withdrawal",
+            Type::Dispute => "dispute",
+            Type::Resolve => "resolve",
+            Type::Chargeback => "chargeback",
+            Type::ReverseChargeback => "reverse_chargeback",
+            Type::CreditLimit => "credit_limit",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::deserialize(
+            serde::de::IntoDeserializer::<serde::de::value::Error>::into_deserializer(name),
+        )
+        .ok()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
 pub struct Transaction {
     #[serde(rename = "type")]
     pub t_type: Type,
@@ -26,6 +53,23 @@ pub struct Transaction {
     #[serde(default)]
     #[serde(deserialize_with = "de_decimal_non_negative")]
     pub amount: Option<Amount>,
+    /// Optional unix timestamp (seconds), used by time based dispute windows.
+    #[serde(default)]
+    pub timestamp: Option<Timestamp>,
+}
+
+/// Decimal places an amount may have.
+pub const AMOUNT_SCALE: u32 = 4;
+
+/// Rules every input amount follows, whatever the input format.
+pub fn validate_amount(amount: Amount) -> Result<Amount, &'static str> {
+    if amount.is_sign_negative() {
+        Err("amount must be non-negative")
+    } else if amount.scale() > AMOUNT_SCALE {
+        Err("amount must be up to four decimals")
+    } else {
+        Ok(amount)
+    }
 }
 
 fn de_decimal_non_negative<'de, D>(deserializer: D) -> Result<Option<Amount>, D::Error>
@@ -34,18 +78,9 @@ where
 {
     let opt = Option::<Amount>::deserialize(deserializer)?;
 
-    if let Some(v) = opt {
-        if v.is_sign_negative() {
-            return Err(serde::de::Error::custom("amount must be non-negative"));
-        } else if v.scale() > 4 {
-            return Err(serde::de::Error::custom(
-                "amount must be up to four decimals",
-            ));
-        }
-        Ok(Some(v))
-    } else {
-        Ok(None)
-    }
+    opt.map(validate_amount)
+        .transpose()
+        .map_err(serde::de::Error::custom)
 }
 
 #[cfg(test)]
@@ -69,6 +104,7 @@ pub mod tests {
             t_client_id: 1,
             transaction_id: 1,
             amount: Some(dec!(10.50)),
+            timestamp: None,
         };
 
         let transaction = rdr.deserialize::<Transaction>().next().unwrap();
@@ -90,6 +126,7 @@ pub mod tests {
             t_client_id: 1,
             transaction_id: 2,
             amount: Some(dec!(10.5555)),
+            timestamp: None,
         };
 
         let transaction = rdr.deserialize::<Transaction>().next().unwrap();
@@ -157,6 +194,7 @@ pub mod tests {
             t_client_id: 1,
             transaction_id: 100,
             amount: Some(dec!(10.50)),
+            timestamp: None,
         };
 
         let transaction = rdr.deserialize::<Transaction>().next().unwrap();
@@ -178,6 +216,7 @@ pub mod tests {
             t_client_id: 1,
             transaction_id: 100,
             amount: None,
+            timestamp: None,
         };
 
         let transaction = rdr.deserialize::<Transaction>().next().unwrap();
@@ -185,6 +224,35 @@ pub mod tests {
         assert_eq!(expected, transaction.unwrap());
     }
 
+    #[test]
+    fn read_timestamp_column() {
+        let csv_data = "type,client,tx,amount,timestamp\n\
+                        deposit,1,1,10.50,1700000000\n\
+                        dispute,1,1,,1700000100\n";
+
+        let mut rdr = csv::ReaderBuilder::new()
+            .flexible(true)
+            .from_reader(csv_data.as_bytes());
+
+        let transactions: Vec<Transaction> = rdr.deserialize().collect::<Result<_, _>>().unwrap();
+        assert_eq!(transactions[0].timestamp, Some(1_700_000_000));
+        assert_eq!(transactions[1].amount, None);
+        assert_eq!(transactions[1].timestamp, Some(1_700_000_100));
+    }
+
+    #[test]
+    fn read_without_timestamp_column() {
+        let csv_data = "type, client, tx, amount\ndeposit, 1, 1, 1.0\n";
+
+        let mut rdr = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .flexible(true)
+            .from_reader(csv_data.as_bytes());
+
+        let transaction = rdr.deserialize::<Transaction>().next().unwrap();
+        assert_eq!(transaction.unwrap().timestamp, None);
+    }
+
     #[test]
     fn read_chargeback_transaction() {
         let csv_data = "chargeback,1,100\n";
@@ -199,10 +267,48 @@ pub mod tests {
             t_client_id: 1,
             transaction_id: 100,
             amount: None,
+            timestamp: None,
         };
 
         let transaction = rdr.deserialize::<Transaction>().next().unwrap();
         assert!(transaction.is_ok());
         assert_eq!(expected, transaction.unwrap());
     }
+
+    #[test]
+    fn read_reverse_chargeback_transaction() {
+        let csv_data = "reverse_chargeback,1,100\n";
+
+        let mut rdr = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader(csv_data.as_bytes());
+
+        let expected = Transaction {
+            t_type: Type::ReverseChargeback,
+            t_client_id: 1,
+            transaction_id: 100,
+            amount: None,
+            timestamp: None,
+        };
+
+        let transaction = rdr.deserialize::<Transaction>().next().unwrap();
+        assert!(transaction.is_ok());
+        assert_eq!(expected, transaction.unwrap());
+    }
+
+    #[test]
+    fn type_names() {
+        for t_type in [
+            Type::Deposit,
+            Type::Withdrawal,
+            Type::Dispute,
+            Type::Resolve,
+            Type::Chargeback,
+            Type::ReverseChargeback,
+            Type::CreditLimit,
+        ] {
+            assert_eq!(Type::from_name(t_type.name()), Some(t_type));
+        }
+        assert_eq!(Type::from_name("bogus"), None);
+    }
 }
@@ -1,5 +1,14 @@
 use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
 
 pub type ClientId = u16;
 pub type TransactionId = u32;
 pub type Amount = Decimal;
+pub type Timestamp = u64;
+
+/// Where an event happened: its position in the processed rows and its timestamp, if any.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventPosition {
+    pub sequence: u64,
+    pub timestamp: Option<Timestamp>,
+}
@@ -0,0 +1,350 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::StatusCode;
+use reqwest::header::CONTENT_TYPE;
+
+use crate::webhooks::error::WebhookError;
+use crate::webhooks::outbox::{Delivery, Outbox};
+use crate::webhooks::signature::{SIGNATURE_HEADER, TIMESTAMP_HEADER, sign};
+use crate::webhooks::webhook_config::{WebhookEndpoint, WebhooksConfig};
+
+/// Header carrying the event id, the same for every attempt.
+pub const ID_HEADER: &str = "x-webhook-id";
+/// Header carrying the event name.
+pub const EVENT_HEADER: &str = "x-webhook-event";
+
+/// Current unix time in milliseconds.
+pub fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |now| now.as_millis() as u64)
+}
+
+/// Why an attempt failed, and whether trying again may help.
+struct Failure {
+    retry: bool,
+    message: String,
+}
+
+/// POSTs the deliveries of the outbox. A delivery is done once its endpoint answers
+/// with a 2xx status. Timeouts, connection errors, 408, 429 and 5xx answers are
+/// retried after an exponential backoff, up to `max_attempts`; other answers, and
+/// endpoints removed from the configuration, give the delivery up at once.
+pub struct WebhookDispatcher {
+    config: WebhooksConfig,
+    outbox: Arc<Outbox>,
+    client: reqwest::Client,
+}
+
+impl WebhookDispatcher {
+    pub fn new(config: WebhooksConfig, outbox: Arc<Outbox>) -> Result<Self, WebhookError> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(config.timeout_ms))
+            .build()?;
+        Ok(Self {
+            config,
+            outbox,
+            client,
+        })
+    }
+
+    /// Stores the queued deliveries, makes every attempt due now and returns the number
+    /// of deliveries left.
+    pub async fn deliver_due(&self) -> usize {
+        let outbox = self.outbox.clone();
+        match tokio::task::spawn_blocking(move || outbox.store_queued()).await {
+            Ok(Err(err)) => eprintln!("{}", err),
+            Err(err) => eprintln!("Error storing webhooks: {}", err),
+            Ok(Ok(_)) => {}
+        }
+        for (key, delivery) in self.outbox.due(now_ms()) {
+            self.deliver(&key, delivery).await;
+        }
+        self.outbox.len()
+    }
+
+    /// Delivers for as long as the returned future is polled: as deliveries are added
+    /// and as their retries fall due.
+    pub async fn run(&self) {
+        loop {
+            self.deliver_due().await;
+            let next = self.outbox.next_attempt_ms();
+            let wait = Duration::from_millis(next.unwrap_or(0).saturating_sub(now_ms()));
+            tokio::select! {
+                _ = self.outbox.added() => {}
+                _ = tokio::time::sleep(wait), if next.is_some() => {}
+            }
+        }
+    }
+
+    async fn deliver(&self, key: &str, mut delivery: Delivery) {
+        let result = match self.config.endpoint(&delivery.url) {
+            Some(endpoint) => self.post(endpoint, &delivery).await,
+            None => Err(Failure {
+                retry: false,
+                message: "endpoint no longer configured".to_string(),
+            }),
+        };
+        delivery.attempts += 1;
+        let stored = match result {
+            Ok(()) => self.outbox.remove(key),
+            Err(failure) => {
+                delivery.last_error = Some(failure.message);
+                if failure.retry && delivery.attempts < self.config.max_attempts {
+                    delivery.next_attempt_ms = now_ms() + self.config.backoff_ms(delivery.attempts);
+                    self.outbox.update(key, delivery)
+                } else {
+                    eprintln!(
+                        "Webhook {} to {} given up after {} attempts: {}",
+                        delivery.id,
+                        delivery.url,
+                        delivery.attempts,
+                        delivery.last_error.as_deref().unwrap_or_default()
+                    );
+                    self.outbox.fail(key, delivery)
+                }
+            }
+        };
+        if let Err(err) = stored {
+            eprintln!("{}", err);
+        }
+    }
+
+    async fn post(&self, endpoint: &WebhookEndpoint, delivery: &Delivery) -> Result<(), Failure> {
+        let timestamp = now_ms() / 1000;
+        let response = self
+            .client
+            .post(&endpoint.url)
+            .header(CONTENT_TYPE, "application/json")
+            .header(ID_HEADER, &delivery.id)
+            .header(EVENT_HEADER, &delivery.event)
+            .header(TIMESTAMP_HEADER, timestamp)
+            .header(
+                SIGNATURE_HEADER,
+                sign(&endpoint.secret, timestamp, delivery.body.as_bytes()),
+            )
+            .body(delivery.body.clone())
+            .send()
+            .await
+            .map_err(|err| Failure {
+                retry: true,
+                message: err.to_string(),
+            })?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        Err(Failure {
+            retry: status.is_server_error()
+                || status == StatusCode::REQUEST_TIMEOUT
+                || status == StatusCode::TOO_MANY_REQUESTS,
+            message: format!("endpoint answered {}", status),
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use axum::Router;
+    use axum::extract::State;
+    use axum::http::HeaderMap;
+    use axum::routing::post;
+    use rust_decimal::dec;
+
+    use super::*;
+    use crate::engine::payments_engine::PaymentsEngine;
+    use crate::transaction::{Transaction, Type};
+    use crate::webhooks::signature::verify;
+    use crate::webhooks::webhook_config::WebhookEvent;
+    use crate::webhooks::webhook_observer::WebhookObserver;
+
+    /// Local stand-in for a webhook receiver: answers `failures` requests with
+    /// `failure`, then 200, and keeps every request.
+    #[derive(Clone)]
+    struct StandIn {
+        failures: Arc<AtomicUsize>,
+        failure: StatusCode,
+        requests: Arc<Mutex<Vec<(HeaderMap, String)>>>,
+    }
+
+    impl StandIn {
+        async fn start(failures: usize, failure: StatusCode) -> (Self, String) {
+            let stand_in = Self {
+                failures: Arc::new(AtomicUsize::new(failures)),
+                failure,
+                requests: Arc::new(Mutex::new(Vec::new())),
+            };
+            let router = Router::new()
+                .route("/hooks", post(receive))
+                .with_state(stand_in.clone());
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}/hooks", listener.local_addr().unwrap());
+            tokio::spawn(async move { axum::serve(listener, router).await });
+            (stand_in, url)
+        }
+
+        fn requests(&self) -> Vec<(HeaderMap, String)> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    async fn receive(
+        State(stand_in): State<StandIn>,
+        headers: HeaderMap,
+        body: String,
+    ) -> StatusCode {
+        stand_in.requests.lock().unwrap().push((headers, body));
+        match stand_in
+            .failures
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| {
+                left.checked_sub(1)
+            }) {
+            Ok(_) => stand_in.failure,
+            Err(_) => StatusCode::OK,
+        }
+    }
+
+    fn config(name: &str, url: String) -> WebhooksConfig {
+        WebhooksConfig {
+            outbox: std::env::temp_dir().join(format!("{}_{}", name, std::process::id())),
+            max_attempts: 4,
+            initial_backoff_ms: 10,
+            max_backoff_ms: 50,
+            timeout_ms: 2_000,
+            endpoints: vec![WebhookEndpoint {
+                url,
+                events: vec![WebhookEvent::AccountLocked],
+                secret: "s3cret".to_string(),
+            }],
+        }
+    }
+
+    /// Locks client 1's account with a chargeback, with `config`'s webhooks subscribed.
+    async fn lock_account(config: &WebhooksConfig) -> Arc<Outbox> {
+        let outbox = Arc::new(Outbox::open(&config.outbox).unwrap());
+        let payments_engine = PaymentsEngine::new();
+        payments_engine
+            .subscribe(Arc::new(WebhookObserver::new(
+                config.endpoints.clone(),
+                outbox.clone(),
+            )))
+            .await;
+        let rows = [
+            (Type::Deposit, Some(dec!(10))),
+            (Type::Dispute, None),
+            (Type::Chargeback, None),
+        ];
+        for (t_type, amount) in rows {
+            payments_engine
+                .handle_transaction(Transaction {
+                    t_type,
+                    t_client_id: 1,
+                    transaction_id: 1,
+                    amount,
+                    timestamp: None,
+                })
+                .await
+                .unwrap();
+        }
+        outbox
+    }
+
+    #[tokio::test]
+    async fn retries_until_accepted() {
+        let (stand_in, url) = StandIn::start(2, StatusCode::SERVICE_UNAVAILABLE).await;
+        let config = config("webhooks_retried", url);
+        let outbox = lock_account(&config).await;
+        // Only the lock matches the filter
+        assert_eq!(outbox.len(), 1);
+
+        let dispatcher = WebhookDispatcher::new(config.clone(), outbox.clone()).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            tokio::select! {
+                _ = dispatcher.run() => {}
+                _ = async {
+                    while !outbox.is_empty() {
+                        tokio::time::sleep(Duration::from_millis(5)).await;
+                    }
+                } => {}
+            }
+        })
+        .await
+        .unwrap();
+
+        let requests = stand_in.requests();
+        assert_eq!(requests.len(), 3);
+        let (headers, body) = &requests[2];
+        assert_eq!(headers[ID_HEADER], requests[0].0[ID_HEADER]);
+        assert_eq!(headers[EVENT_HEADER], "account_locked");
+        let timestamp = headers[TIMESTAMP_HEADER].to_str().unwrap().parse().unwrap();
+        let signature = headers[SIGNATURE_HEADER].to_str().unwrap();
+        assert!(verify("s3cret", timestamp, body.as_bytes(), signature));
+        let payload: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(payload["event"], "account_locked");
+        assert_eq!(payload["change"]["transaction"]["type"], "chargeback");
+        assert_eq!(payload["change"]["after"]["locked"], true);
+        std::fs::remove_dir_all(&config.outbox).unwrap();
+    }
+
+    #[tokio::test]
+    async fn ids_differ_across_runs() {
+        let (_, url) = StandIn::start(0, StatusCode::OK).await;
+        let first = config("webhooks_first_run", url.clone());
+        let second = config("webhooks_second_run", url);
+        let mut ids = Vec::new();
+        for config in [&first, &second] {
+            // The same rows at the same positions, as in two runs without a state file
+            let outbox = lock_account(config).await;
+            outbox.store_queued().unwrap();
+            ids.push(outbox.due(now_ms())[0].1.id.clone());
+            std::fs::remove_dir_all(&config.outbox).unwrap();
+        }
+        assert_ne!(ids[0], ids[1]);
+    }
+
+    #[tokio::test]
+    async fn gives_up() {
+        let (stand_in, url) = StandIn::start(usize::MAX, StatusCode::BAD_REQUEST).await;
+        let config = config("webhooks_refused", url);
+        let outbox = lock_account(&config).await;
+        let dispatcher = WebhookDispatcher::new(config.clone(), outbox.clone()).unwrap();
+        // Refused: not retried
+        assert_eq!(dispatcher.deliver_due().await, 0);
+        assert_eq!(stand_in.requests().len(), 1);
+        assert_eq!(
+            std::fs::read_dir(config.outbox.join("failed"))
+                .unwrap()
+                .count(),
+            1
+        );
+        std::fs::remove_dir_all(&config.outbox).unwrap();
+
+        // Unreachable: retried until the attempts run out
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        drop(listener);
+        let mut config = config;
+        config.endpoints[0].url = format!("http://{}/hooks", address);
+        let outbox = lock_account(&config).await;
+        let dispatcher = WebhookDispatcher::new(config.clone(), outbox.clone()).unwrap();
+        for _ in 0..config.max_attempts {
+            tokio::time::sleep(Duration::from_millis(config.max_backoff_ms)).await;
+            dispatcher.deliver_due().await;
+        }
+        assert!(outbox.is_empty());
+        let failed = std::fs::read_dir(config.outbox.join("failed"))
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        let delivery: Delivery =
+            serde_json::from_slice(&std::fs::read(failed.path()).unwrap()).unwrap();
+        assert_eq!(delivery.attempts, config.max_attempts);
+        assert!(delivery.last_error.is_some());
+        std::fs::remove_dir_all(&config.outbox).unwrap();
+    }
+}
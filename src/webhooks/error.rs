@@ -0,0 +1,13 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum WebhookError {
+    #[error("Webhook outbox error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid webhook delivery: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Webhook client error: {0}")]
+    Client(#[from] reqwest::Error),
+}
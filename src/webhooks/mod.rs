@@ -0,0 +1,14 @@
+//! Outbound webhooks: account changes matching an endpoint's event filter are kept in
+//! a durable outbox and POSTed to the endpoint, signed, until it accepts them.
+
+#[cfg(feature = "webhooks")]
+pub mod dispatcher;
+#[cfg(feature = "webhooks")]
+pub mod error;
+#[cfg(feature = "webhooks")]
+pub mod outbox;
+#[cfg(feature = "webhooks")]
+pub mod signature;
+pub mod webhook_config;
+#[cfg(feature = "webhooks")]
+pub mod webhook_observer;
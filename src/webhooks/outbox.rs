@@ -0,0 +1,276 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+
+use crate::webhooks::error::WebhookError;
+
+/// An event waiting to be POSTed to one endpoint.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Delivery {
+    /// Identifier of the event, the same for every attempt and endpoint, so that
+    /// receivers can drop the duplicates of an at-least-once delivery.
+    pub id: String,
+    pub event: String,
+    pub url: String,
+    /// JSON request body, fixed when the event happened.
+    pub body: String,
+    /// Attempts made so far.
+    pub attempts: u32,
+    /// Earliest time of the next attempt, unix milliseconds.
+    pub next_attempt_ms: u64,
+    pub last_error: Option<String>,
+}
+
+/// Durable queue of deliveries: one JSON file per delivery in a directory, written
+/// before the delivery is attempted and removed once the endpoint accepted it.
+/// Deliveries given up are moved to its `failed` subdirectory.
+///
+/// Deliveries may also be queued in memory first, where writing them would block the
+/// caller; `store_queued` writes them later.
+pub struct Outbox {
+    dir: PathBuf,
+    pending: Mutex<BTreeMap<String, Delivery>>,
+    queued: Mutex<Vec<Delivery>>,
+    next_key: AtomicU64,
+    added: Notify,
+}
+
+impl Outbox {
+    /// Opens the outbox in `dir`, created if needed, with the deliveries left by a
+    /// previous run.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, WebhookError> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(dir.join("failed"))?;
+        let mut pending = BTreeMap::new();
+        for file in std::fs::read_dir(&dir)? {
+            let path = file?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "json")
+                && let Some(key) = path.file_stem().and_then(|stem| stem.to_str())
+            {
+                let delivery = serde_json::from_slice(&std::fs::read(&path)?)?;
+                pending.insert(key.to_string(), delivery);
+            }
+        }
+        // Keys sort in the order deliveries were added, the next one continues after
+        // every key used, given up ones included
+        let mut next_key = pending
+            .keys()
+            .next_back()
+            .and_then(|key| key.parse::<u64>().ok())
+            .map_or(0, |key| key + 1);
+        for file in std::fs::read_dir(dir.join("failed"))? {
+            if let Some(key) = file?
+                .path()
+                .file_stem()
+                .and_then(|stem| stem.to_str()?.parse::<u64>().ok())
+            {
+                next_key = next_key.max(key + 1);
+            }
+        }
+        Ok(Self {
+            dir,
+            pending: Mutex::new(pending),
+            queued: Mutex::new(Vec::new()),
+            next_key: AtomicU64::new(next_key),
+            added: Notify::new(),
+        })
+    }
+
+    /// Stores `delivery` and wakes the dispatcher.
+    pub fn add(&self, delivery: Delivery) -> Result<(), WebhookError> {
+        let key = format!("{:020}", self.next_key.fetch_add(1, Ordering::Relaxed));
+        self.write(&key, &delivery)?;
+        self.pending.lock().unwrap().insert(key, delivery);
+        self.added.notify_one();
+        Ok(())
+    }
+
+    /// Keeps `delivery` in memory until `store_queued` and wakes the dispatcher. Does not
+    /// touch the disk, so it may be called while the engine holds its locks.
+    pub fn queue(&self, delivery: Delivery) {
+        self.queued.lock().unwrap().push(delivery);
+        self.added.notify_one();
+    }
+
+    /// Stores the queued deliveries, in the order they were queued. Returns how many
+    /// were stored; those not stored because of an error stay queued.
+    pub fn store_queued(&self) -> Result<usize, WebhookError> {
+        let mut queued = std::mem::take(&mut *self.queued.lock().unwrap()).into_iter();
+        let mut stored = 0;
+        while let Some(delivery) = queued.next() {
+            if let Err(err) = self.add(delivery.clone()) {
+                // Back in front of those queued meanwhile
+                let mut left: Vec<Delivery> = std::iter::once(delivery).chain(queued).collect();
+                let mut current = self.queued.lock().unwrap();
+                left.append(&mut current);
+                *current = left;
+                return Err(err);
+            }
+            stored += 1;
+        }
+        Ok(stored)
+    }
+
+    /// Records a failed attempt of the delivery stored under `key`.
+    pub fn update(&self, key: &str, delivery: Delivery) -> Result<(), WebhookError> {
+        self.write(key, &delivery)?;
+        self.pending
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), delivery);
+        Ok(())
+    }
+
+    /// Drops a delivery the endpoint accepted.
+    pub fn remove(&self, key: &str) -> Result<(), WebhookError> {
+        self.pending.lock().unwrap().remove(key);
+        std::fs::remove_file(self.path(key))?;
+        Ok(())
+    }
+
+    /// Gives up a delivery, keeping it with its last error in `failed`.
+    pub fn fail(&self, key: &str, delivery: Delivery) -> Result<(), WebhookError> {
+        self.write(key, &delivery)?;
+        self.pending.lock().unwrap().remove(key);
+        std::fs::rename(
+            self.path(key),
+            self.dir.join("failed").join(format!("{}.json", key)),
+        )?;
+        Ok(())
+    }
+
+    /// Deliveries whose next attempt is due at `now_ms`, oldest first.
+    pub fn due(&self, now_ms: u64) -> Vec<(String, Delivery)> {
+        self.pending
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, delivery)| delivery.next_attempt_ms <= now_ms)
+            .map(|(key, delivery)| (key.clone(), delivery.clone()))
+            .collect()
+    }
+
+    /// When the next attempt is due, if any delivery is left.
+    pub fn next_attempt_ms(&self) -> Option<u64> {
+        self.pending
+            .lock()
+            .unwrap()
+            .values()
+            .map(|delivery| delivery.next_attempt_ms)
+            .min()
+    }
+
+    /// Deliveries not done yet, stored or queued.
+    pub fn len(&self) -> usize {
+        self.pending.lock().unwrap().len() + self.queued.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Completes once a delivery was added since the last call.
+    pub async fn added(&self) {
+        self.added.notified().await
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    /// Writes the file of `key` whole or not at all: through a temporary file, synced
+    /// before it replaces the previous version.
+    fn write(&self, key: &str, delivery: &Delivery) -> Result<(), WebhookError> {
+        let temporary = self.dir.join(format!("{}.tmp", key));
+        let mut file = std::fs::File::create(&temporary)?;
+        file.write_all(&serde_json::to_vec(delivery)?)?;
+        file.sync_all()?;
+        std::fs::rename(&temporary, self.path(key))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    pub fn delivery(id: &str, next_attempt_ms: u64) -> Delivery {
+        Delivery {
+            id: id.to_string(),
+            event: "account_locked".to_string(),
+            url: "http://localhost/hooks".to_string(),
+            body: "{}".to_string(),
+            attempts: 0,
+            next_attempt_ms,
+            last_error: None,
+        }
+    }
+
+    #[test]
+    fn survives_restarts() {
+        let dir = std::env::temp_dir().join(format!("outbox_{}", std::process::id()));
+        let outbox = Outbox::open(&dir).unwrap();
+        outbox.add(delivery("1", 0)).unwrap();
+        outbox.add(delivery("2", 500)).unwrap();
+        outbox.add(delivery("3", 0)).unwrap();
+        let due = outbox.due(100);
+        assert_eq!(due.len(), 2);
+        assert_eq!(outbox.next_attempt_ms(), Some(0));
+
+        outbox.remove(&due[0].0).unwrap();
+        outbox.fail(&due[1].0, due[1].1.clone()).unwrap();
+        drop(outbox);
+
+        let outbox = Outbox::open(&dir).unwrap();
+        assert_eq!(outbox.len(), 1);
+        assert_eq!(outbox.due(1_000)[0].1.id, "2");
+        assert!(
+            dir.join("failed")
+                .join(format!("{}.json", due[1].0))
+                .exists()
+        );
+        // Keys keep growing after a restart
+        outbox.add(delivery("4", 0)).unwrap();
+        assert_eq!(outbox.due(1_000)[1].1.id, "4");
+
+        // Nor do they restart once only given up deliveries are left
+        for (key, _) in outbox.due(1_000) {
+            outbox.remove(&key).unwrap();
+        }
+        drop(outbox);
+        let outbox = Outbox::open(&dir).unwrap();
+        outbox.add(delivery("5", 0)).unwrap();
+        assert!(outbox.due(0)[0].0 > due[1].0);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn queued_until_stored() {
+        let dir = std::env::temp_dir().join(format!("outbox_queued_{}", std::process::id()));
+        let outbox = Outbox::open(&dir).unwrap();
+        outbox.queue(delivery("1", 0));
+        outbox.queue(delivery("2", 0));
+        assert_eq!(outbox.len(), 2);
+        assert!(outbox.due(0).is_empty());
+
+        assert_eq!(outbox.store_queued().unwrap(), 2);
+        assert_eq!(outbox.store_queued().unwrap(), 0);
+        let ids: Vec<String> = outbox
+            .due(0)
+            .into_iter()
+            .map(|(_, delivery)| delivery.id)
+            .collect();
+        assert_eq!(ids, ["1", "2"]);
+        drop(outbox);
+
+        assert_eq!(Outbox::open(&dir).unwrap().len(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,144 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::engine::events::AccountChanged;
+use crate::transaction::Type;
+
+/// Outbound webhooks. Nothing is sent, and no outbox created, without endpoints.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebhooksConfig {
+    /// Directory keeping every delivery until its endpoint accepted it or it was given
+    /// up, so that none is lost across restarts.
+    pub outbox: PathBuf,
+    /// Attempts per delivery, the first one included, before it is given up.
+    pub max_attempts: u32,
+    /// Wait before the first retry, doubled after each failed attempt.
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    /// Longest an endpoint may take to answer.
+    pub timeout_ms: u64,
+    pub endpoints: Vec<WebhookEndpoint>,
+}
+
+impl Default for WebhooksConfig {
+    fn default() -> Self {
+        Self {
+            outbox: PathBuf::from("webhooks_outbox"),
+            max_attempts: 8,
+            initial_backoff_ms: 1_000,
+            max_backoff_ms: 300_000,
+            timeout_ms: 10_000,
+            endpoints: Vec::new(),
+        }
+    }
+}
+
+impl WebhooksConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.endpoints.is_empty()
+    }
+
+    /// Wait before the next attempt of a delivery that failed `attempts` times.
+    pub fn backoff_ms(&self, attempts: u32) -> u64 {
+        let doublings = attempts.saturating_sub(1).min(63);
+        self.initial_backoff_ms
+            .saturating_mul(1 << doublings)
+            .min(self.max_backoff_ms)
+    }
+
+    pub fn endpoint(&self, url: &str) -> Option<&WebhookEndpoint> {
+        self.endpoints.iter().find(|endpoint| endpoint.url == url)
+    }
+}
+
+/// Where to POST the events, which ones, and the secret requests are signed with.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookEndpoint {
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+    pub secret: String,
+}
+
+/// Events an endpoint can ask for: an account getting locked or unlocked, or any row
+/// of a type being applied, by its name in the input files.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    AccountLocked,
+    AccountUnlocked,
+    #[serde(untagged)]
+    Applied(Type),
+}
+
+impl WebhookEvent {
+    /// Events of `change`: the row applied, then the lock change if any.
+    pub fn of(change: &AccountChanged) -> Vec<Self> {
+        let mut events = vec![WebhookEvent::Applied(change.transaction.t_type)];
+        match (change.before.locked, change.after.locked) {
+            (false, true) => events.push(WebhookEvent::AccountLocked),
+            (true, false) => events.push(WebhookEvent::AccountUnlocked),
+            _ => {}
+        }
+        events
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            WebhookEvent::AccountLocked => "account_locked",
+            WebhookEvent::AccountUnlocked => "account_unlocked",
+            WebhookEvent::Applied(t_type) => t_type.name(),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::config::engine_config::EngineConfig;
+
+    #[test]
+    fn webhooks_config() {
+        let config = EngineConfig::from_toml(
+            r#"
+            [webhooks]
+            outbox = "outbox"
+            initial_backoff_ms = 100
+
+            [[webhooks.endpoints]]
+            url = "https://fraud.example.com/hooks"
+            events = ["account_locked", "chargeback"]
+            secret = "s3cret"
+            "#,
+        )
+        .unwrap();
+        let webhooks = &config.webhooks;
+        assert!(webhooks.is_enabled());
+        assert_eq!(webhooks.max_attempts, 8);
+        assert_eq!(
+            webhooks.endpoints[0].events,
+            vec![
+                WebhookEvent::AccountLocked,
+                WebhookEvent::Applied(Type::Chargeback)
+            ]
+        );
+        assert_eq!(webhooks.backoff_ms(1), 100);
+        assert_eq!(webhooks.backoff_ms(3), 400);
+        assert_eq!(webhooks.backoff_ms(40), 300_000);
+
+        assert!(
+            EngineConfig::from_toml(
+                r#"
+                [[webhooks.endpoints]]
+                url = "http://localhost"
+                events = ["locked"]
+                secret = ""
+                "#
+            )
+            .is_err()
+        );
+        assert!(!EngineConfig::default().webhooks.is_enabled());
+    }
+}
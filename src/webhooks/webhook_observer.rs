@@ -0,0 +1,95 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde::Serialize;
+
+use crate::engine::events::AccountChanged;
+use crate::observe::account_observer::AccountObserver;
+use crate::webhooks::dispatcher::now_ms;
+use crate::webhooks::outbox::{Delivery, Outbox};
+use crate::webhooks::webhook_config::{WebhookEndpoint, WebhookEvent};
+
+/// Observers created so far by this process, part of their run id.
+static OBSERVERS: AtomicU64 = AtomicU64::new(0);
+
+/// Request body of every webhook.
+#[derive(Serialize)]
+struct Payload<'a> {
+    id: &'a str,
+    event: &'a str,
+    change: &'a AccountChanged,
+}
+
+/// Queues the events each endpoint asked for in the outbox, one delivery per endpoint.
+/// Observers are called with the engine locked, so the deliveries are only queued in
+/// memory here; the dispatcher writes them to the outbox before their first attempt.
+pub struct WebhookObserver {
+    endpoints: Vec<WebhookEndpoint>,
+    outbox: Arc<Outbox>,
+    /// Prefix of the ids of this observer's events. Row positions start again in every
+    /// run without a state file, so ids also name the run: its start time, the process
+    /// and the observer.
+    run: String,
+}
+
+impl WebhookObserver {
+    pub fn new(endpoints: Vec<WebhookEndpoint>, outbox: Arc<Outbox>) -> Self {
+        let run = format!(
+            "{:x}-{:x}-{:x}",
+            now_ms(),
+            std::process::id(),
+            OBSERVERS.fetch_add(1, Ordering::Relaxed)
+        );
+        Self {
+            endpoints,
+            outbox,
+            run,
+        }
+    }
+}
+
+impl AccountObserver for WebhookObserver {
+    fn account_changed(&self, change: &AccountChanged) {
+        for event in WebhookEvent::of(change) {
+            let endpoints: Vec<&WebhookEndpoint> = self
+                .endpoints
+                .iter()
+                .filter(|endpoint| endpoint.events.contains(&event))
+                .collect();
+            if endpoints.is_empty() {
+                continue;
+            }
+            let id = format!(
+                "{}-{}-{}-{}-{}",
+                self.run,
+                change.position.sequence,
+                change.client_id,
+                change.transaction.transaction_id,
+                event.name()
+            );
+            let body = match serde_json::to_string(&Payload {
+                id: &id,
+                event: event.name(),
+                change,
+            }) {
+                Ok(body) => body,
+                Err(err) => {
+                    eprintln!("Error encoding webhook {}: {}", id, err);
+                    continue;
+                }
+            };
+            for endpoint in endpoints {
+                let delivery = Delivery {
+                    id: id.clone(),
+                    event: event.name().to_string(),
+                    url: endpoint.url.clone(),
+                    body: body.clone(),
+                    attempts: 0,
+                    next_attempt_ms: now_ms(),
+                    last_error: None,
+                };
+                self.outbox.queue(delivery);
+            }
+        }
+    }
+}